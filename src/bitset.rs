@@ -0,0 +1,87 @@
+//! Bit-packed boolean arrays allocated in an arena.
+
+use std::fmt;
+
+/// A fixed-length array of bits, packed eight to a byte. Returned by
+/// `Allocator::alloc_bitset()`.
+pub struct ArenaBitset<'a> {
+    bytes: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> ArenaBitset<'a> {
+    // `bytes` must be zeroed, and hold at least `len` bits.
+    pub(crate) fn new(bytes: &'a mut [u8], len: usize) -> ArenaBitset<'a> {
+        ArenaBitset { bytes, len }
+    }
+
+    /// Get the bit at `index`. Panics if it is out of bounds.
+    pub fn get(&self, index: usize) -> bool {
+        self.check(index);
+        self.bytes[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// Set the bit at `index`. Panics if it is out of bounds.
+    pub fn set(&mut self, index: usize, value: bool) {
+        self.check(index);
+        let mask = 1 << (index % 8);
+        if value {
+            self.bytes[index / 8] |= mask;
+        } else {
+            self.bytes[index / 8] &= !mask;
+        }
+    }
+
+    /// Get the number of bits that are set.
+    pub fn count_ones(&self) -> usize {
+        self.bytes.iter().map(|byte| byte.count_ones() as usize).sum()
+    }
+
+    /// Get the number of bits.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no bits at all.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn check(&self, index: usize) {
+        if index >= self.len {
+            panic!("index out of bounds: the len is {} but the index is {}", self.len, index);
+        }
+    }
+}
+
+impl<'a> fmt::Debug for ArenaBitset<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("ArenaBitset {{ len: {}, ones: {} }}", self.len, self.count_ones()))
+    }
+}
+
+
+#[test]
+fn bits_across_bytes() {
+    let mut arena = super::Arena::new();
+    let mut allocator = arena.allocator();
+    let mut bits = allocator.alloc_bitset(20);
+    assert_eq!(bits.len(), 20);
+    assert_eq!(bits.count_ones(), 0);
+
+    for &index in &[0, 7, 8, 15, 16, 19] {
+        bits.set(index, true);
+    }
+    bits.set(8, false);
+    assert!(bits.get(0) && bits.get(7) && bits.get(15) && bits.get(19));
+    assert!(!bits.get(8) && !bits.get(1) && !bits.get(18));
+    assert_eq!(bits.count_ones(), 5);
+}
+
+#[test]
+#[should_panic(expected = "index out of bounds")]
+fn bit_out_of_bounds() {
+    let mut arena = super::Arena::new();
+    let bits = arena.allocator().alloc_bitset(10);
+    bits.get(10);
+}
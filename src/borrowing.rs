@@ -0,0 +1,88 @@
+//! Arenas that allocate into a buffer they borrow, never touching the heap.
+
+use std::fmt;
+use std::mem;
+use std::ptr;
+use std::slice;
+
+use super::{padding_for, AllocError};
+
+/// A fixed-capacity arena that bump-allocates into a borrowed buffer.
+/// Obtained from `Arena::from_slice()`.
+///
+/// It never grows and never allocates from the heap: once the buffer is
+/// full, allocations fail with `AllocError::BudgetExceeded`. Allocations
+/// borrow from the buffer, so they can outlive the `BorrowingArena` but
+/// not the buffer.
+pub struct BorrowingArena<'buf> {
+    // The part of the buffer not handed out yet.
+    remaining: &'buf mut [u8],
+}
+
+impl<'buf> BorrowingArena<'buf> {
+    pub(crate) fn new(buf: &'buf mut [u8]) -> BorrowingArena<'buf> {
+        BorrowingArena { remaining: buf }
+    }
+
+    fn alloc_raw(&mut self, size: usize, align: usize) -> Result<*mut u8, AllocError> {
+        let padding = padding_for(self.remaining.as_ptr() as usize, align);
+        let needed = padding.checked_add(size).ok_or(AllocError::BudgetExceeded)?;
+        if needed > self.remaining.len() {
+            return Err(AllocError::BudgetExceeded);
+        }
+        let remaining = mem::take(&mut self.remaining);
+        let (taken, rest) = remaining.split_at_mut(needed);
+        self.remaining = rest;
+        Ok(taken[padding..].as_mut_ptr())
+    }
+
+    /// Allocate a copy of an object, or fail if the buffer is full.
+    pub fn alloc<T: Copy>(&mut self, elem: T) -> Result<&'buf mut T, AllocError> {
+        let memory = self.alloc_raw(mem::size_of::<T>(), mem::align_of::<T>())? as *mut T;
+        unsafe {
+            ptr::write(memory, elem);
+            Ok(&mut *memory)
+        }
+    }
+
+    /// Allocate a copy of a slice, or fail if the buffer is full.
+    pub fn alloc_slice<T: Copy>(&mut self, elems: &[T]) -> Result<&'buf mut [T], AllocError> {
+        let byte_count = mem::size_of::<T>().checked_mul(elems.len()).ok_or(AllocError::BudgetExceeded)?;
+        let memory = self.alloc_raw(byte_count, mem::align_of::<T>())? as *mut T;
+        unsafe {
+            ptr::copy_nonoverlapping(elems.as_ptr(), memory, elems.len());
+            Ok(slice::from_raw_parts_mut(memory, elems.len()))
+        }
+    }
+
+    /// Get the number of bytes of the buffer not yet handed out.
+    pub fn remaining(&self) -> usize {
+        self.remaining.len()
+    }
+}
+
+impl<'buf> fmt::Debug for BorrowingArena<'buf> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("BorrowingArena {{ remaining: {} }}", self.remaining()))
+    }
+}
+
+
+#[test]
+fn stack_buffer() {
+    let mut buf = [0u8; 256];
+    let mut arena = super::Arena::from_slice(&mut buf);
+
+    let a = arena.alloc(1u8).unwrap();
+    let b = arena.alloc(2u64).unwrap();
+    let text = arena.alloc_slice(b"hello").unwrap();
+    assert_eq!(b as *mut u64 as usize % mem::align_of::<u64>(), 0);
+    *a += 1;
+    assert_eq!((*a, *b, &text[..]), (2, 2, &b"hello"[..]));
+
+    let rest = arena.remaining();
+    assert_eq!(arena.alloc_slice(&vec![0u8; rest + 1]).unwrap_err(), AllocError::BudgetExceeded);
+    assert!(arena.alloc_slice(&vec![7u8; rest]).is_ok());
+    assert_eq!(arena.remaining(), 0);
+    assert!(arena.alloc(0u8).is_err());
+}
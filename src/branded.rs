@@ -0,0 +1,80 @@
+//! Arenas whose references carry a brand, so they can't be mixed up with
+//! references from another arena.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use super::Allocator;
+
+// Invariant in 'id, so one brand can never be coerced into another.
+type Brand<'id> = PhantomData<fn(&'id ()) -> &'id ()>;
+
+/// An allocator whose allocations are identified by `BrandedRef`s that
+/// only it accepts. Obtained from `Arena::branded()`.
+pub struct BrandedArena<'id> {
+    allocator: Allocator<'id>,
+    brand: Brand<'id>,
+}
+
+/// A handle to a value allocated by the `BrandedArena` with the same
+/// brand `'id`.
+///
+/// Handles are `Copy`, so they can be stored freely in other arena
+/// values; reading or writing through one takes the arena that made it.
+pub struct BrandedRef<'id, T> {
+    ptr: NonNull<T>,
+    brand: Brand<'id>,
+}
+
+impl<'id> BrandedArena<'id> {
+    pub(crate) fn new(allocator: Allocator<'id>) -> BrandedArena<'id> {
+        BrandedArena { allocator, brand: PhantomData }
+    }
+
+    /// Allocate a copy of an object
+    pub fn alloc<T: Copy + 'id>(&mut self, elem: T) -> BrandedRef<'id, T> {
+        BrandedRef {
+            ptr: NonNull::from(self.allocator.alloc(elem)),
+            brand: PhantomData,
+        }
+    }
+
+    /// Borrow the value behind `r`.
+    pub fn get<T>(&self, r: BrandedRef<'id, T>) -> &T {
+        unsafe { &*r.ptr.as_ptr() }
+    }
+
+    /// Mutably borrow the value behind `r`.
+    pub fn get_mut<T>(&mut self, r: BrandedRef<'id, T>) -> &mut T {
+        unsafe { &mut *r.ptr.as_ptr() }
+    }
+}
+
+impl<'id> fmt::Debug for BrandedArena<'id> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("BrandedArena {{ allocator: {:?} }}", self.allocator))
+    }
+}
+
+impl<'id, T> Clone for BrandedRef<'id, T> {
+    fn clone(&self) -> BrandedRef<'id, T> {
+        *self
+    }
+}
+
+impl<'id, T> Copy for BrandedRef<'id, T> {}
+
+impl<'id, T> PartialEq for BrandedRef<'id, T> {
+    fn eq(&self, other: &BrandedRef<'id, T>) -> bool {
+        self.ptr == other.ptr
+    }
+}
+
+impl<'id, T> Eq for BrandedRef<'id, T> {}
+
+impl<'id, T> fmt::Debug for BrandedRef<'id, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("BrandedRef({:p})", self.ptr))
+    }
+}
@@ -0,0 +1,56 @@
+//! Values built up in place in arena memory.
+
+use std::fmt;
+
+/// A default value already in the arena, being modified in place. Returned
+/// by `Allocator::build()`.
+///
+/// Each `with()` mutates the arena's copy directly, so the value is never
+/// built on the stack and copied in.
+pub struct ArenaBuild<'a, T: 'a> {
+    value: &'a mut T,
+}
+
+impl<'a, T: Copy> ArenaBuild<'a, T> {
+    pub(crate) fn new(value: &'a mut T) -> ArenaBuild<'a, T> {
+        ArenaBuild { value }
+    }
+
+    /// Modify the value in place.
+    pub fn with<F: FnOnce(&mut T)>(self, f: F) -> ArenaBuild<'a, T> {
+        f(&mut *self.value);
+        self
+    }
+
+    /// Get a reference to the finished value.
+    pub fn done(self) -> &'a mut T {
+        self.value
+    }
+}
+
+impl<'a, T> fmt::Debug for ArenaBuild<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("ArenaBuild {{ address: {:p} }}", self.value))
+    }
+}
+
+
+#[test]
+fn build_field_by_field() {
+    #[derive(Copy, Clone, Default, Debug, PartialEq)]
+    struct Header {
+        version: u16,
+        flags: u32,
+        length: u64,
+    }
+
+    let mut arena = super::Arena::new();
+    let mut allocator = arena.allocator();
+    let header = allocator.build::<Header>()
+        .with(|h| h.version = 3)
+        .with(|h| h.flags |= 0x10)
+        .with(|h| h.length = h.version as u64 * 100)
+        .done();
+    assert_eq!(*header, Header { version: 3, flags: 0x10, length: 300 });
+    assert!(allocator.arena.contains(header as *const Header));
+}
@@ -0,0 +1,61 @@
+//! Slices that are either borrowed or copied into an arena.
+
+use std::fmt;
+use std::ops::Deref;
+
+/// A slice that is either borrowed from elsewhere or owned by an arena,
+/// like `std::borrow::Cow` but without a heap allocation for the owned
+/// case. Returned by `Allocator::to_arena_cow()`.
+pub enum ArenaCow<'a, T: 'a> {
+    /// A slice borrowed from outside the arena.
+    Borrowed(&'a [T]),
+    /// A copy allocated in the arena.
+    Owned(&'a mut [T]),
+}
+
+impl<'a, T> ArenaCow<'a, T> {
+    /// Whether the slice was copied into the arena.
+    pub fn is_owned(&self) -> bool {
+        match *self {
+            ArenaCow::Borrowed(_) => false,
+            ArenaCow::Owned(_) => true,
+        }
+    }
+}
+
+impl<'a, T> Deref for ArenaCow<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match *self {
+            ArenaCow::Borrowed(slice) => slice,
+            ArenaCow::Owned(ref slice) => slice,
+        }
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for ArenaCow<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+
+#[test]
+fn cow_borrows_or_copies() {
+    let mut arena = super::Arena::new();
+    let mut allocator = arena.allocator();
+    let data = [1u32, 2, 3];
+
+    let borrowed = allocator.to_arena_cow(&data, false);
+    assert!(!borrowed.is_owned());
+    assert_eq!(borrowed.as_ptr(), data.as_ptr());
+    assert_eq!(&*borrowed, &data);
+
+    let owned = allocator.to_arena_cow(&data, true);
+    assert!(owned.is_owned());
+    assert_ne!(owned.as_ptr(), data.as_ptr());
+    assert!(allocator.arena.contains(owned.as_ptr()));
+    assert_eq!(owned.len(), 3);
+    assert_eq!(owned[2], 3);
+}
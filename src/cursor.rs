@@ -0,0 +1,88 @@
+//! Slices filled in one element at a time, over as long as it takes.
+
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::slice;
+
+/// A slice allocated ahead of time and filled in order through `next()`.
+/// Returned by `Allocator::alloc_fill_cursor()`.
+///
+/// The cursor doesn't borrow the allocator, so it can be stored and filled
+/// as values turn up, while the allocator goes on making other
+/// allocations. It can't outlive the arena it came from.
+pub struct FillCursor<'a, T: 'a> {
+    slots: &'a mut [MaybeUninit<T>],
+    filled: usize,
+}
+
+impl<'a, T: Copy> FillCursor<'a, T> {
+    pub(crate) fn new(slots: &'a mut [MaybeUninit<T>]) -> FillCursor<'a, T> {
+        FillCursor { slots, filled: 0 }
+    }
+
+    /// Write the next element. Panics if every element is already written.
+    pub fn next(&mut self, value: T) {
+        assert!(!self.is_complete(), "FillCursor is already full");
+        self.slots[self.filled] = MaybeUninit::new(value);
+        self.filled += 1;
+    }
+
+    /// Get the number of elements written so far.
+    pub fn filled(&self) -> usize {
+        self.filled
+    }
+
+    /// Get the number of elements the slice will hold once complete.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Whether the slice has no elements at all.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Whether every element has been written.
+    pub fn is_complete(&self) -> bool {
+        self.filled == self.slots.len()
+    }
+
+    /// Finish filling, getting back the elements written so far. That is
+    /// the whole slice if the cursor is complete.
+    pub fn into_slice(self) -> &'a mut [T] {
+        let filled = self.filled;
+        unsafe { slice::from_raw_parts_mut(self.slots.as_mut_ptr() as *mut T, filled) }
+    }
+}
+
+impl<'a, T> fmt::Debug for FillCursor<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("FillCursor {{ filled: {}, len: {} }}", self.filled, self.slots.len()))
+    }
+}
+
+
+#[test]
+fn fill_through_stored_cursor() {
+    use super::Arena;
+
+    struct Collector<'a> {
+        squares: FillCursor<'a, u32>,
+    }
+
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let mut collector = Collector { squares: allocator.alloc_fill_cursor(4) };
+    for i in 0..4u32 {
+        assert!(!collector.squares.is_complete());
+        collector.squares.next(i * i);
+        allocator.alloc(0xFFu8);
+    }
+    assert!(collector.squares.is_complete());
+    assert_eq!(collector.squares.into_slice(), &[0, 1, 4, 9]);
+
+    let mut partial = allocator.alloc_fill_cursor::<u8>(3);
+    partial.next(7);
+    assert_eq!(partial.filled(), 1);
+    assert_eq!(partial.into_slice(), &[7]);
+}
@@ -0,0 +1,234 @@
+//! An arena that fills each chunk from both ends.
+
+use std::cmp;
+use std::fmt;
+use std::mem;
+use std::ptr;
+
+use super::{padding_for, AllocError};
+
+// A chunk's front allocations occupy data[..front] and its back allocations
+// data[back..]; the gap between them is free.
+struct Chunk {
+    data: Vec<u8>,
+    front: usize,
+    back: usize,
+}
+
+impl Chunk {
+    fn new(capacity: usize) -> Chunk {
+        Chunk { data: vec![0; capacity], front: 0, back: capacity }
+    }
+
+    fn place_front(&mut self, size: usize, align: usize) -> Option<*mut u8> {
+        let start = self.data.as_mut_ptr() as usize;
+        let at = self.front + padding_for(start + self.front, align);
+        let end = at.checked_add(size)?;
+        if end > self.back {
+            return None;
+        }
+        self.front = end;
+        Some((start + at) as *mut u8)
+    }
+
+    fn place_back(&mut self, size: usize, align: usize) -> Option<*mut u8> {
+        let start = self.data.as_mut_ptr() as usize;
+        let address = (start + self.back).checked_sub(size)? & !(align - 1);
+        if address < start + self.front {
+            return None;
+        }
+        self.back = address - start;
+        Some(address as *mut u8)
+    }
+}
+
+/// An arena where each chunk is filled from the front and the back at
+/// once, e.g. fixed-size records from the front and the variable-size data
+/// they refer to from the back, without needing two arenas.
+///
+/// Allocation happens through a `DoubleEndedAllocator` returned from
+/// `allocator()`. When an allocation from either end would run into the
+/// other end's allocations, a new, larger chunk is added, unless the arena
+/// was made with `fixed()`, in which case the allocation fails.
+pub struct DoubleEndedArena {
+    // Oldest chunk first; only the last one is allocated from.
+    chunks: Vec<Chunk>,
+    fixed: bool,
+}
+
+impl DoubleEndedArena {
+    /// Construct a new DoubleEndedArena with a first chunk of 1000 bytes.
+    pub fn new() -> DoubleEndedArena {
+        DoubleEndedArena::with_capacity(1000)
+    }
+
+    /// Construct a new DoubleEndedArena whose first chunk holds `capacity`
+    /// bytes.
+    ///
+    /// The chosen capacity does not limit the final size of the arena.
+    pub fn with_capacity(capacity: usize) -> DoubleEndedArena {
+        DoubleEndedArena { chunks: vec![Chunk::new(capacity)], fixed: false }
+    }
+
+    /// Construct a DoubleEndedArena with a single chunk of `capacity` bytes
+    /// that never grows: once the two ends meet, allocations fail with
+    /// `AllocError::BudgetExceeded`.
+    pub fn fixed(capacity: usize) -> DoubleEndedArena {
+        DoubleEndedArena { chunks: vec![Chunk::new(capacity)], fixed: true }
+    }
+
+    /// Construct a DoubleEndedAllocator for this arena.
+    pub fn allocator(&mut self) -> DoubleEndedAllocator<'_> {
+        DoubleEndedAllocator { arena: self }
+    }
+
+    /// Get the number of chunks the arena has.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Get the number of bytes free between the two ends of the current
+    /// chunk.
+    pub fn gap(&self) -> usize {
+        let last = self.chunks.last().expect("DoubleEndedArena always has a chunk");
+        last.back - last.front
+    }
+
+    fn alloc_raw(&mut self, size: usize, align: usize, back: bool) -> Result<*mut u8, AllocError> {
+        if let Some(x) = self.place(size, align, back) {
+            return Ok(x);
+        }
+        if self.fixed {
+            return Err(AllocError::BudgetExceeded);
+        }
+        let last = self.chunks.last().expect("DoubleEndedArena always has a chunk").data.len();
+        let needed = size.checked_add(align).ok_or(AllocError::BudgetExceeded)?;
+        self.chunks.push(Chunk::new(cmp::max(last.saturating_mul(2), needed)));
+        Ok(self.place(size, align, back).expect("DoubleEndedArena's new chunk is too small for the allocation"))
+    }
+
+    fn place(&mut self, size: usize, align: usize, back: bool) -> Option<*mut u8> {
+        let last = self.chunks.last_mut().expect("DoubleEndedArena always has a chunk");
+        if back { last.place_back(size, align) } else { last.place_front(size, align) }
+    }
+}
+
+/// Allocates from both ends of a `DoubleEndedArena`'s chunks. Returned by
+/// `DoubleEndedArena::allocator()`.
+///
+/// Chunks never move, so allocations live as long as the arena borrow does
+/// and any number of them can be held at once, e.g. front records holding
+/// references to back data.
+pub struct DoubleEndedAllocator<'a> {
+    arena: &'a mut DoubleEndedArena,
+}
+
+impl<'a> DoubleEndedAllocator<'a> {
+    /// Allocate a copy of an object at the front of the current chunk.
+    ///
+    /// Panics if a fixed arena is full.
+    pub fn alloc_front<T: Copy>(&mut self, elem: T) -> &'a mut T {
+        self.try_alloc_front(elem).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Allocate a copy of an object at the back of the current chunk.
+    ///
+    /// Panics if a fixed arena is full.
+    pub fn alloc_back<T: Copy>(&mut self, elem: T) -> &'a mut T {
+        self.try_alloc_back(elem).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `alloc_front`, but fails instead of panicking if a fixed arena
+    /// is full.
+    pub fn try_alloc_front<T: Copy>(&mut self, elem: T) -> Result<&'a mut T, AllocError> {
+        let memory = self.arena.alloc_raw(mem::size_of::<T>(), mem::align_of::<T>(), false)? as *mut T;
+        unsafe {
+            ptr::write(memory, elem);
+            Ok(&mut *memory)
+        }
+    }
+
+    /// Like `alloc_back`, but fails instead of panicking if a fixed arena
+    /// is full.
+    pub fn try_alloc_back<T: Copy>(&mut self, elem: T) -> Result<&'a mut T, AllocError> {
+        let memory = self.arena.alloc_raw(mem::size_of::<T>(), mem::align_of::<T>(), true)? as *mut T;
+        unsafe {
+            ptr::write(memory, elem);
+            Ok(&mut *memory)
+        }
+    }
+
+    /// Get the number of chunks, as `DoubleEndedArena::chunk_count()`
+    /// reports it.
+    pub fn chunk_count(&self) -> usize {
+        self.arena.chunk_count()
+    }
+
+    /// Get the free bytes between the two ends of the current chunk, as
+    /// `DoubleEndedArena::gap()` reports it.
+    pub fn gap(&self) -> usize {
+        self.arena.gap()
+    }
+}
+
+impl<'a> fmt::Debug for DoubleEndedAllocator<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("DoubleEndedAllocator {{ arena: {:?} }}", self.arena))
+    }
+}
+
+impl Default for DoubleEndedArena {
+    fn default() -> DoubleEndedArena {
+        DoubleEndedArena::new()
+    }
+}
+
+impl fmt::Debug for DoubleEndedArena {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("DoubleEndedArena {{ chunks: {}, gap: {} }}",
+                                 self.chunks.len(), self.gap()))
+    }
+}
+
+
+#[test]
+fn ends_meet_then_grow() {
+    #[derive(Copy, Clone)]
+    struct Record<'a> {
+        id: u32,
+        data: &'a u64,
+    }
+
+    let mut arena = DoubleEndedArena::with_capacity(128);
+    let mut allocator = arena.allocator();
+    let mut records = Vec::new();
+    for i in 0..4u32 {
+        let data = allocator.alloc_back(i as u64 * 10);
+        records.push(allocator.alloc_front(Record { id: i, data }));
+    }
+    assert_eq!(allocator.chunk_count(), 1);
+
+    while allocator.chunk_count() == 1 {
+        allocator.alloc_back([0u8; 3]);
+    }
+    assert_eq!(allocator.gap(), 256 - 3);
+    for (i, record) in records.iter().enumerate() {
+        assert_eq!(record.id, i as u32);
+        assert_eq!(*record.data, i as u64 * 10);
+    }
+}
+
+#[test]
+fn fixed_arena_fails_when_ends_meet() {
+    let mut arena = DoubleEndedArena::fixed(16);
+    let mut allocator = arena.allocator();
+    let first = allocator.alloc_front(1u64);
+    assert!(allocator.try_alloc_back(2u32).is_ok());
+    assert!(allocator.try_alloc_back(3u16).is_ok());
+    assert_eq!(allocator.try_alloc_front(4u32).err(), Some(AllocError::BudgetExceeded));
+    assert_eq!(*allocator.alloc_back(5u16), 5);
+    assert_eq!(allocator.gap(), 0);
+    assert_eq!(allocator.try_alloc_back(6u8).err(), Some(AllocError::BudgetExceeded));
+    assert_eq!(*first, 1);
+    assert_eq!(arena.chunk_count(), 1);
+}
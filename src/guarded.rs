@@ -0,0 +1,103 @@
+//! References that notice, in debug builds, when their arena was reset.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A reference to a value in an arena that remembers the arena's
+/// generation. Returned by `Allocator::alloc_guarded()`.
+///
+/// Resetting or dropping the arena starts a new generation, and in debug
+/// builds dereferencing a guard from an older one panics. The borrow
+/// checker already rules that out for safe code; this catches it when the
+/// arena was reached through raw pointers.
+pub struct Guarded<'a, T: 'a> {
+    value: *mut T,
+    generation: usize,
+    current: Arc<AtomicUsize>,
+    borrow: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Guarded<'a, T> {
+    pub(crate) fn new(value: &'a mut T, current: Arc<AtomicUsize>) -> Guarded<'a, T> {
+        let generation = current.load(Ordering::Relaxed);
+        Guarded { value, generation, current, borrow: PhantomData }
+    }
+
+    /// Whether the arena has been reset or dropped since the value was
+    /// allocated.
+    pub fn is_stale(&self) -> bool {
+        self.current.load(Ordering::Relaxed) != self.generation
+    }
+
+    fn check(&self) {
+        debug_assert!(!self.is_stale(), "Guarded value used after its arena was reset");
+    }
+}
+
+impl<'a, T> Deref for Guarded<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.check();
+        unsafe { &*self.value }
+    }
+}
+
+impl<'a, T> DerefMut for Guarded<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.check();
+        unsafe { &mut *self.value }
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for Guarded<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_stale() {
+            f.write_str("Guarded(<stale>)")
+        } else {
+            f.debug_tuple("Guarded").field(&**self).finish()
+        }
+    }
+}
+
+
+#[test]
+fn guard_reads_while_fresh() {
+    let mut arena = super::Arena::new();
+    let mut allocator = arena.allocator();
+    let mut guard = allocator.alloc_guarded(5u32);
+    *guard += 1;
+    assert_eq!(*guard, 6);
+    assert!(!guard.is_stale());
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "after its arena was reset")]
+fn stale_guard_panics() {
+    let mut arena = super::Arena::new();
+    let arena_ptr: *mut super::Arena = &mut arena;
+    // Going through a raw pointer hides the borrow from the borrow checker,
+    // which would otherwise reject the reset below.
+    let guard = unsafe { (*arena_ptr).allocator().alloc_guarded(5u32) };
+    arena.reset();
+    assert!(guard.is_stale());
+    let _ = *guard;
+}
+
+#[test]
+fn absorbed_guard_follows_new_arena() {
+    let mut first = super::Arena::new();
+    let mut second = super::Arena::new();
+    let second_ptr: *mut super::Arena = &mut second;
+    // As above, the raw pointer lets second be absorbed with a guard out.
+    let guard = unsafe { (*second_ptr).allocator().alloc_guarded(5u32) };
+    first.absorb(second);
+    assert!(!guard.is_stale());
+    assert_eq!(*guard, 5);
+    first.reset();
+    assert!(guard.is_stale());
+}
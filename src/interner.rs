@@ -0,0 +1,66 @@
+//! Deduplicated strings allocated in an arena.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use super::Allocator;
+
+/// Hands out one arena-allocated copy of each distinct string, so equal
+/// strings interned through it share the same memory.
+pub struct Interner<'a> {
+    allocator: Allocator<'a>,
+    // Every string interned so far. The keys borrow from the arena, which
+    // outlives the interner.
+    strings: HashSet<&'a str>,
+}
+
+impl<'a> Interner<'a> {
+    /// Construct an Interner that allocates through `allocator`.
+    pub fn new(allocator: Allocator<'a>) -> Interner<'a> {
+        Interner { allocator, strings: HashSet::new() }
+    }
+
+    /// Get the arena's copy of `s`, allocating one if `s` hasn't been
+    /// interned before.
+    pub fn intern(&mut self, s: &str) -> &'a str {
+        if let Some(&interned) = self.strings.get(s) {
+            return interned;
+        }
+        let interned: &'a str = self.allocator.alloc_str(s);
+        self.strings.insert(interned);
+        interned
+    }
+
+    /// Get the number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether no strings have been interned.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+impl<'a> fmt::Debug for Interner<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("Interner {{ len: {} }}", self.len()))
+    }
+}
+
+
+#[test]
+fn intern_dedups() {
+    let mut arena = super::Arena::new();
+    let mut interner = Interner::new(arena.allocator());
+
+    let first = interner.intern("alpha");
+    let owned = String::from("alpha");
+    let second = interner.intern(&owned);
+    let other = interner.intern("beta");
+
+    assert_eq!(first.as_ptr(), second.as_ptr());
+    assert!(first.as_ptr() != other.as_ptr());
+    assert_eq!((first, other), ("alpha", "beta"));
+    assert_eq!(interner.len(), 2);
+}
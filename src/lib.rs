@@ -56,26 +56,115 @@
 //!
 //! ```
 
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+
 use std::mem;
+use std::mem::MaybeUninit;
 use std::cmp;
-use std::usize;
 use std::default::Default;
+use std::ptr;
+use std::ptr::NonNull;
+#[cfg(feature = "debug-track")]
+use std::panic::Location;
 use std::slice;
+use std::str;
+use std::error::Error;
 use std::fmt;
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::iter;
+use std::vec;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+pub mod typed;
+mod small;
+mod branded;
+mod borrowing;
+mod storage;
+mod interner;
+mod snapshot;
+mod slot;
+mod string_table;
+mod padded;
+mod sharded;
+mod record;
+mod cursor;
+mod guarded;
+mod pool;
+mod patch;
+mod cow;
+mod bitset;
+mod tree;
+mod double_ended;
+mod build;
+mod scratch;
 
-struct Chunk {
-    data: Vec<u8>,
-    next: Option<Box<Chunk>>,
+pub use typed::TypedArena;
+pub use small::SmallArenaSlice;
+pub use branded::{BrandedArena, BrandedRef};
+pub use borrowing::BorrowingArena;
+pub use storage::ChunkStorage;
+pub use interner::Interner;
+pub use snapshot::{ArenaMetrics, Snapshot, TuningHint, UsageDelta};
+pub use slot::{SlotArena, SlotId};
+pub use string_table::StringTable;
+pub use padded::PaddedSlice;
+pub use sharded::ShardedArena;
+pub use record::RecordBuilder;
+pub use cursor::FillCursor;
+pub use guarded::Guarded;
+pub use pool::PoolArena;
+pub use patch::Patch;
+pub use cow::ArenaCow;
+pub use bitset::ArenaBitset;
+pub use tree::TreeNode;
+pub use double_ended::{DoubleEndedAllocator, DoubleEndedArena};
+pub use build::ArenaBuild;
+pub use scratch::ScratchBuffer;
+
+struct Chunk<S> {
+    data: S,
+    // The logical offset of this chunk's first byte: how many bytes all
+    // older chunks held when this one was added.
+    base_offset: usize,
+    next: Option<Box<Chunk<S>>>,
 }
 
-impl Chunk {
+/// The byte new chunks are filled with. With the `debug-poison` feature this
+/// is a recognizable pattern, so reads of memory that was never written
+/// stand out.
+#[cfg(not(feature = "debug-poison"))]
+const CHUNK_FILL: u8 = 0;
+#[cfg(feature = "debug-poison")]
+const CHUNK_FILL: u8 = 0xCD;
+
+impl<S: ChunkStorage> Chunk<S> {
+    fn new(capacity: usize) -> Chunk<S> {
+        Chunk {
+            data: S::with_capacity(capacity),
+            base_offset: 0,
+            next: None,
+        }
+    }
+
     fn attempt_alloc(&mut self, size: usize, align: usize) -> Option<*mut u8> {
-        let start = round_up(self.data.len(), align);
+        // Align the actual address rather than the offset, since the
+        // storage's buffer itself is only guaranteed to be byte-aligned.
+        let len = self.data.len();
+        let padding = padding_for(self.data.as_ptr() as usize + len, align);
+        let free = self.data.capacity() - len;
 
-        if size <= self.data.capacity() && start <= self.data.capacity() - size {
+        if padding <= free && size <= free - padding {
+            let start = len + padding;
             Some(unsafe {
                 self.data.set_len(start + size);
-                self.data.as_mut_ptr().offset(start as isize)
+                self.data.as_mut_ptr().add(start)
             })
         } else {
             None
@@ -83,254 +172,5134 @@ impl Chunk {
     }
 }
 
+/// The settings an `Arena` is constructed with.
+///
+/// This plays the part of a builder: set the fields that matter, take the
+/// rest from `..Default::default()`, and pass it to `Arena::with_config()`,
+/// or to `Arena::try_with_config()` to get `AllocError::OutOfMemory`
+/// rather than an abort if the first chunk can't be allocated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ArenaConfig {
+    /// The capacity, in bytes, of the arena's first chunk.
+    pub initial_capacity: usize,
+    /// The most bytes the arena's chunks may hold in total.
+    pub max_total_bytes: usize,
+    /// Every allocation's size and alignment are rounded up to a multiple
+    /// of this, which must be a power of two.
+    pub granularity: usize,
+    /// The most elements a single slice allocation may have, e.g. to
+    /// reject untrusted length prefixes before trying to allocate.
+    pub max_slice_len: usize,
+    /// The most bytes a single allocation may take, e.g. so every
+    /// allocation fits in a chunk of known size. Larger allocations are
+    /// rejected rather than given an oversized chunk of their own.
+    pub max_single_allocation: usize,
+    /// The largest alignment methods taking an explicit alignment, like
+    /// `Allocator::alloc_aligned()`, accept, so a bad alignment argument
+    /// can't waste most of a chunk on padding or ask for an absurd chunk.
+    /// Alignments required by types themselves aren't limited.
+    pub max_alignment: usize,
+    /// Whether to round every chunk's capacity up to a multiple of
+    /// `PAGE_SIZE`, so no chunk ends partway through a page. Chunks are
+    /// only rounded up as far as the budget allows.
+    pub page_aligned_chunks: bool,
+    /// How `Arena::ensure_capacity()` makes room when the current chunk is
+    /// partly filled.
+    pub grow_mode: GrowMode,
+    /// Whether to allocate the first chunk when the arena is constructed.
+    /// If not, the arena starts with no memory at all, and the first
+    /// allocation that needs room allocates a chunk of `initial_capacity`
+    /// bytes (or more, if it needs more), so an arena that is never used
+    /// never touches the heap.
+    pub eager: bool,
+}
+
+/// How an arena makes room in a partly filled chunk, set by
+/// `ArenaConfig::grow_mode`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum GrowMode {
+    /// Add a new chunk after the current one, leaving whatever room was
+    /// left in the current one unused. Nothing allocated ever moves.
+    #[default]
+    Chain,
+    /// Have `Arena::ensure_capacity()` move the current chunk's contents
+    /// into one bigger buffer, like `Vec` does, so they stay contiguous.
+    ///
+    /// This moves what was allocated in the current chunk. The borrow
+    /// checker makes sure no references into the arena are alive, since
+    /// `ensure_capacity()` takes `&mut Arena`, but pointers from
+    /// `Allocator::alloc_nonnull()` or `alloc_raw()` into that chunk are
+    /// left dangling, and `RelOffset`s into it don't carry over. Don't
+    /// combine this mode with code that holds on to either. Growth during
+    /// allocation still adds chunks, since references are alive then.
+    Realloc,
+}
+
+/// The page size chunk capacities are rounded up to a multiple of when
+/// `ArenaConfig::page_aligned_chunks` is set.
+pub const PAGE_SIZE: usize = 4096;
+
+impl ArenaConfig {
+    // The capacity to actually give a chunk asked to hold `capacity` bytes,
+    // if the budget leaves room for a chunk of up to `room` bytes.
+    fn chunk_capacity(&self, capacity: usize, room: usize) -> usize {
+        if !self.page_aligned_chunks {
+            return capacity;
+        }
+        match capacity.checked_add(PAGE_SIZE - 1) {
+            Some(end) if end & !(PAGE_SIZE - 1) <= cmp::min(room, isize::MAX as usize) => {
+                end & !(PAGE_SIZE - 1)
+            }
+            _ => capacity,
+        }
+    }
+}
+
+impl Default for ArenaConfig {
+    fn default() -> ArenaConfig {
+        ArenaConfig {
+            initial_capacity: 1000,
+            max_total_bytes: usize::MAX,
+            granularity: 1,
+            max_slice_len: usize::MAX,
+            max_single_allocation: usize::MAX,
+            max_alignment: PAGE_SIZE,
+            page_aligned_chunks: false,
+            grow_mode: GrowMode::Chain,
+            eager: true,
+        }
+    }
+}
+
+/// The reasons a fallible allocation can fail.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AllocError {
+    /// Satisfying the allocation would grow the arena past its budget.
+    BudgetExceeded,
+    /// Satisfying the allocation would grow the arena while it is frozen.
+    Frozen,
+    /// The slice has more elements than the arena's `max_slice_len`.
+    SliceTooLong,
+    /// The allocation is larger than the arena's `max_single_allocation`.
+    AllocationTooLarge,
+    /// The requested alignment is larger than the arena's `max_alignment`.
+    AlignmentTooLarge,
+    /// The system allocator could not provide the memory.
+    OutOfMemory,
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AllocError::BudgetExceeded => f.write_str("Arena memory budget exceeded"),
+            AllocError::Frozen => f.write_str("Arena is frozen and cannot grow"),
+            AllocError::SliceTooLong => f.write_str("Arena slice is longer than allowed"),
+            AllocError::AllocationTooLarge => f.write_str("Arena allocation is larger than allowed"),
+            AllocError::AlignmentTooLarge => f.write_str("Arena alignment is larger than allowed"),
+            AllocError::OutOfMemory => f.write_str("Arena could not allocate memory"),
+        }
+    }
+}
+
+impl Error for AllocError {}
+
 /// Holds the backing memory for allocated objects out of itself.
 ///
 /// Actual allocation into the `Arena` happens via an `Allocator` returned
 /// from `allocator()`.
-pub struct Arena {
-    head: Chunk,
+///
+/// Chunks are `Vec<u8>`s unless another `ChunkStorage` is chosen with
+/// `Arena::with_storage()`.
+pub struct Arena<S: ChunkStorage = Vec<u8>> {
+    config: ArenaConfig,
+    head: Chunk<S>,
+    // Sum of every chunk's capacity, so capacity() need not walk the list.
+    total_capacity: usize,
+    // The index alloc_indexed will hand out next.
+    next_index: usize,
+    // The bytes emit has handed out so far.
+    emitted: usize,
+    // The number alloc_numbered will hand out next.
+    sequence: u64,
+    // Picked at random when the arena is constructed, to tag allocations
+    // from alloc_tagged() with.
+    id: u64,
+    // Whether a chunk has ever been added, even if reset() dropped it since.
+    grew: bool,
+    // The allocation count when the first chunk was added.
+    first_growth_at: Option<usize>,
+    // Whether growing is forbidden for now, by Allocator::freeze().
+    frozen: bool,
+    // Capacity past which growing panics, set by set_panic_on_growth_over().
+    tripwire: usize,
+    // Bytes skipped to align allocations, over the arena's whole life.
+    padding_bytes: usize,
+    // Allocations made, over the arena's whole life.
+    allocation_count: usize,
+    // The largest alignment any allocation has asked for.
+    max_align: usize,
+    // The most bytes_used and chunks seen before any were given back.
+    peak_bytes: usize,
+    peak_chunks: usize,
+    // Destructors of alloc_reset_dropped() values living in the chunks, in
+    // the order the values were allocated. A reset runs them.
+    drops: Vec<DropEntry>,
+    // The same for alloc_arena_dropped() values, which a reset moves to
+    // kept instead, since their destructors only run with the arena's.
+    arena_drops: Vec<ArenaDropEntry>,
+    // Values alloc_arena_dropped() moved out of the chunks on a reset, to
+    // drop along with the arena.
+    kept: Vec<Kept>,
+    // Positions saved by Allocator::push_mark(), most recent last.
+    marks: Vec<Mark>,
+    // Set by freeze_template(): how far reset() truncates back to.
+    template: Option<Mark>,
+    // Bumped whenever allocations are discarded, once alloc_guarded() has
+    // handed out a guard that needs to know.
+    generation: Option<Arc<AtomicUsize>>,
+    // Generations of arenas absorb() took over, bumped along with this
+    // one's so their guards go stale when this arena discards them.
+    absorbed_generations: Vec<Arc<AtomicUsize>>,
+    // Called after every allocation, if set by set_trace().
+    trace: Option<Trace>,
+    // Where each alloc/alloc_slice call came from, and how many bytes it
+    // asked for.
+    #[cfg(feature = "debug-track")]
+    report: Vec<(Location<'static>, usize)>,
+    // The address and length of every allocation still in the chunks, in
+    // the order they were made.
+    #[cfg(feature = "debug-track")]
+    boundaries: Vec<(usize, usize)>,
+}
+
+/// Where the arena's allocations had reached when `push_mark()` or
+/// `freeze_template()` was called.
+#[derive(Copy, Clone)]
+struct Mark {
+    // The head chunk's buffer address then, to recognize the chunk by.
+    chunk: usize,
+    len: usize,
+    drops: usize,
+    arena_drops: usize,
+}
+
+/// A value in an arena chunk whose destructor has to run before the chunk
+/// is released or reused.
+struct DropEntry {
+    ptr: *mut u8,
+    drop: unsafe fn(*mut u8),
+}
+
+/// A value in an arena chunk whose destructor only runs when the arena is
+/// dropped, along with how to move it out of the chunk when it is
+/// discarded before then.
+struct ArenaDropEntry {
+    entry: DropEntry,
+    keep: unsafe fn(*mut u8) -> Kept,
+}
+
+/// A value moved out of the chunks to outlive a reset. It is only ever
+/// dropped, never accessed.
+struct Kept {
+    _value: Box<dyn Send>,
+}
+
+unsafe impl Sync for Kept {}
+
+// Only values that are themselves Send are registered, and the arena never
+// hands out shared access to them.
+unsafe impl Send for DropEntry {}
+unsafe impl Sync for DropEntry {}
+
+/// A callback given the size, alignment and chunk offset of every
+/// allocation.
+struct Trace(Box<dyn FnMut(usize, usize, usize) + Send>);
+
+// The callback is only ever called through `&mut Arena`, so sharing an
+// arena between threads never shares the callback.
+unsafe impl Sync for Trace {}
+
+unsafe fn drop_value<T>(ptr: *mut u8) {
+    ptr::drop_in_place(ptr as *mut T);
+}
+
+/// Pick an arena id at random. Each call hashes a fresh counter value, so
+/// arenas constructed in quick succession still get unrelated ids.
+fn fresh_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
+
+unsafe fn keep_value<T: Send + 'static>(ptr: *mut u8) -> Kept {
+    Kept { _value: Box::new(ptr::read(ptr as *mut T)) }
 }
 
 impl Arena {
     /// Construct a new Arena. This _does_ allocate an block of memory.
     pub fn new() -> Arena {
-        Arena::with_capacity(1000)
+        Arena::with_config(Default::default())
     }
 
     /// Construct a new Arena with the given initial capacity.
     ///
     /// The chosen capacity does not limit the final size of the arena.
     pub fn with_capacity(capacity: usize) -> Arena {
-        Arena {
-            head: Chunk{
-                data: Vec::with_capacity(capacity),
-                next: None,
-            }
-        }
+        Arena::with_config(ArenaConfig {
+            initial_capacity: capacity,
+            ..Default::default()
+        })
     }
 
-    fn add_chunk(&mut self, chunk_size: usize) {
-        let mut new_head = Chunk {
-            data: Vec::with_capacity(chunk_size),
-            next: None,
-        };
-
-        mem::swap(&mut self.head, &mut new_head);
-        self.head.next = Some(Box::new(new_head));
+    /// Construct a new Arena with the given initial capacity that will never
+    /// hold more than `max_total_bytes` in total.
+    ///
+    /// Allocations that would grow it past the budget fail: the `try_`
+    /// methods return `AllocError::BudgetExceeded`, and the rest panic.
+    pub fn with_budget(capacity: usize, max_total_bytes: usize) -> Arena {
+        Arena::with_config(ArenaConfig {
+            initial_capacity: capacity,
+            max_total_bytes,
+            ..Default::default()
+        })
     }
 
-    /// Construct an Allocator for this arena.
-    pub fn allocator(&mut self) -> Allocator {
-        Allocator {
-            arena: self
-        }
+    /// Construct a new Arena that rounds the size and alignment of every
+    /// allocation up to a multiple of `granularity`, so every allocation
+    /// starts at a multiple of it.
+    ///
+    /// `granularity` must be a power of two. Growing an allocation in place
+    /// (e.g. with `Allocator::grow_slice`) is not possible in such an arena.
+    pub fn with_granularity(granularity: usize) -> Arena {
+        Arena::with_config(ArenaConfig {
+            granularity,
+            ..Default::default()
+        })
     }
 
-    /// Get the number of bytes of memory that have been allocated
-    /// in service of this arena. Not all of this capacity is necessarily
-    /// useful, since asked-for memory may not perfectly fit in the
-    /// underlying blocks allocated.
-    pub fn capacity(&self) -> usize {
-        let mut iter: &Chunk = &self.head;
-        let mut total_capacity = 0;
-        loop {
-            total_capacity += iter.data.capacity();
-            match iter.next {
-                None => { return total_capacity; }
-                Some(ref next) => { iter = next; }
-            }
+    /// Construct an Arena that starts out holding empty chunks of the given
+    /// capacities, rather than growing them on demand.
+    ///
+    /// The first size is the head chunk, which allocations fill first; the
+    /// rest follow it in the chunk list, behind the head like chunks it
+    /// outgrew. Once the head is full a new chunk is added as usual, and the
+    /// preallocated chunks stay in the list (and in `capacity()`) until the
+    /// next `reset()`. Panics if `sizes` is empty.
+    pub fn with_chunks(sizes: &[usize]) -> Arena {
+        assert!(!sizes.is_empty(), "Arena needs at least one chunk");
+        let mut arena = Arena::with_capacity(sizes[0]);
+        let mut tail: Option<Box<Chunk<Vec<u8>>>> = None;
+        for &size in sizes[1..].iter().rev() {
+            let mut chunk = Chunk::new(size);
+            chunk.next = tail;
+            arena.total_capacity += chunk.data.capacity();
+            tail = Some(Box::new(chunk));
         }
+        arena.head.next = tail;
+        arena
     }
-}
 
-impl fmt::Debug for Arena {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_fmt(format_args!("Arena {{ capacity_bytes: {} }}", self.capacity()))
+    /// Construct a new Arena whose chunk capacities are all multiples of
+    /// `PAGE_SIZE` if `page_aligned` is set, e.g. for large arenas where
+    /// partial pages add up.
+    pub fn with_page_aligned_chunks(page_aligned: bool) -> Arena {
+        Arena::with_config(ArenaConfig {
+            page_aligned_chunks: page_aligned,
+            ..Default::default()
+        })
     }
-}
 
-/// Allows allocation out of arena.
-#[derive(Debug)]
-pub struct Allocator<'a> {
-    arena: &'a mut Arena,
-}
+    /// Construct a new Arena configured by `config`.
+    pub fn with_config(config: ArenaConfig) -> Arena {
+        Arena::with_storage(config)
+    }
 
-#[inline]
-fn round_up(base: usize, align: usize) -> usize {
-    (base + (align - 1)) & !(align - 1)
-}
+    /// Like `with_capacity`, but fails with `AllocError::OutOfMemory`
+    /// instead of aborting the process if the first chunk can't be
+    /// allocated.
+    pub fn try_with_capacity(capacity: usize) -> Result<Arena, AllocError> {
+        Arena::try_with_config(ArenaConfig {
+            initial_capacity: capacity,
+            ..Default::default()
+        })
+    }
 
-impl<'a> Allocator<'a> {
-    fn alloc_raw(&mut self, size: usize, align: usize) -> &'a mut u8 {
-        loop {
-            match self.arena.head.attempt_alloc(size, align) {
-                Some(x) => { return unsafe { mem::transmute(x) } },
-                None => {
-                    // Double the current allocation (or the asked for one), but don't overflow.
-                    let minimum_reasonable = cmp::max(self.arena.head.data.len(), size);
-                    let new_chunk_size = 2 * cmp::min(minimum_reasonable, usize::MAX/2);
-                    self.arena.add_chunk(new_chunk_size);
-                }
-            }
+    /// Like `with_config`, but fails with `AllocError::OutOfMemory` instead
+    /// of aborting the process if the first chunk can't be allocated.
+    pub fn try_with_config(config: ArenaConfig) -> Result<Arena, AllocError> {
+        if !config.eager {
+            return Ok(Arena::with_config(config));
         }
+        let capacity = config.chunk_capacity(config.initial_capacity, config.max_total_bytes);
+        let mut data = Vec::new();
+        data.try_reserve_exact(capacity).map_err(|_| AllocError::OutOfMemory)?;
+        data.resize(capacity, CHUNK_FILL);
+        data.clear();
+
+        let mut arena = Arena::with_config(ArenaConfig { initial_capacity: 0, ..config });
+        arena.config.initial_capacity = config.initial_capacity;
+        arena.total_capacity = data.capacity();
+        arena.head.data = data;
+        Ok(arena)
     }
 
-    /// Allocate a copy of an object
-    pub fn alloc<T: Copy>(&mut self, elem: T) -> &'a mut T {
-        let memory = self.alloc_raw(mem::size_of::<T>(), mem::min_align_of::<T>());
-        let res: &'a mut T = unsafe { mem::transmute(memory) };
-        *res = elem;
-        res
+    /// Construct an Arena whose only chunk holds `data`, as though it had
+    /// all been allocated already. Later allocations go after it.
+    ///
+    /// Together with `serialize_graph()` this reloads a saved graph: offsets
+    /// into `data` resolve as `RelOffset`s.
+    pub fn from_vec(mut data: Vec<u8>) -> Arena {
+        // Fill the spare capacity like a fresh chunk's, so padding read back
+        // through chunks() is initialized.
+        let len = data.len();
+        let capacity = data.capacity();
+        data.resize(capacity, CHUNK_FILL);
+        data.truncate(len);
+
+        let mut arena = Arena::with_capacity(0);
+        arena.config.initial_capacity = capacity;
+        arena.total_capacity = capacity;
+        arena.head.data = data;
+        arena
     }
 
-    /// Allocate a default-valued object
-    pub fn alloc_default<T: Copy+Default>(&mut self) -> &'a mut T {
-        self.alloc(Default::default())
+    /// Construct an Arena from the bytes `to_flat_bytes()` returned, e.g.
+    /// on the other side of a process boundary. It has a single chunk
+    /// holding them, so `RelOffset`s into the original arena resolve to the
+    /// same values here.
+    pub fn from_flat_bytes(bytes: Vec<u8>) -> Arena {
+        Arena::from_vec(bytes)
     }
 
-    /// Allocate and leave uninitialized a slice of the given length
-    fn alloc_slice_raw<T>(&mut self, len: usize) -> &'a mut [T] {
-        let element_size = mem::size_of::<[T;2]>() / 2;
-        assert_eq!(mem::size_of::<[T;7]>(), 7 * element_size);
-        let byte_count = element_size.checked_mul(len).expect("Arena slice size overflow");
-        let memory = self.alloc_raw(byte_count, mem::min_align_of::<T>());
-        let res: &'a mut [T] = unsafe { slice::from_raw_parts_mut( mem::transmute(memory), len) };
-        res
+    /// Construct a fixed-capacity arena that allocates into `buf`, without
+    /// any heap allocation of its own, e.g. for a stack array or a
+    /// memory-mapped region you don't own.
+    pub fn from_slice(buf: &mut [u8]) -> BorrowingArena<'_> {
+        BorrowingArena::new(buf)
     }
 
-    /// Allocate a copy of a slice
-    pub fn alloc_slice<T: Copy>(&mut self, elems: &[T]) -> &'a mut [T] {
-        let mut slice = self.alloc_slice_raw(elems.len());
-        for (dest, src) in slice.iter_mut().zip(elems.iter()) {
-            *dest = *src;
+    /// Turn the arena into a boxed slice of its contents without copying,
+    /// for when it holds a single allocated `[T]` filling its only chunk.
+    ///
+    /// This only works for `T`s with an alignment of 1 (like `u8` or byte
+    /// arrays), since the box must free the memory with the layout the
+    /// chunk was allocated with. It also fails, handing the arena back
+    /// unchanged, if the arena has more than one chunk, holds values that
+    /// need dropping, or holds a length that isn't a whole number of `T`s.
+    /// Spare capacity at the end of the chunk is released, which may copy.
+    // Handing the whole arena back on failure is the point.
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_boxed_slice<T: Copy>(mut self) -> Result<Box<[T]>, Arena> {
+        let size = mem::size_of::<T>();
+        if mem::align_of::<T>() != 1 || size == 0 || self.head.next.is_some()
+            || !self.drops.is_empty() || !self.arena_drops.is_empty()
+            || !self.head.data.len().is_multiple_of(size)
+        {
+            return Err(self);
         }
-        slice
+        let data = mem::take(&mut self.head.data).into_boxed_slice();
+        let len = data.len() / size;
+        let bytes = Box::into_raw(data) as *mut u8;
+        Ok(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(bytes as *mut T, len)) })
     }
 
-    /// Allocate and populate a slice, creating each element as a function
-    /// of its index.
-    pub fn alloc_slice_fn<T: Copy, F>(&mut self, len: usize, mut f: F)-> &'a mut [T]
-        where F: FnMut(usize) -> T
-    {
-        let mut slice = self.alloc_slice_raw(len);
-        for (idx, dest) in slice.iter_mut().enumerate() {
-            *dest = f(idx)
+    /// Make sure the current chunk has room for at least `min_capacity`
+    /// more bytes.
+    ///
+    /// If nothing has been allocated in the current chunk yet (as for a
+    /// fresh arena), it is reallocated in place at the larger size, so the
+    /// small initial chunk isn't left behind unused. Otherwise a new chunk
+    /// is added, or with `GrowMode::Realloc` the current chunk's contents
+    /// are moved to a bigger buffer. Panics if this would exceed the
+    /// arena's budget, or if the arena is frozen.
+    pub fn ensure_capacity(&mut self, min_capacity: usize) {
+        let free = self.head.data.capacity() - self.head.data.len();
+        if free >= min_capacity {
+            return;
         }
-        slice
-    }
+        if self.frozen {
+            panic!("{}", AllocError::Frozen);
+        }
+        let extra = if self.head.data.is_empty() { min_capacity - free } else { min_capacity };
+        let remaining_budget = self.config.max_total_bytes.saturating_sub(self.total_capacity);
+        if extra > remaining_budget {
+            panic!("{}", AllocError::BudgetExceeded);
+        }
+        let room = if self.head.data.is_empty() { remaining_budget + free } else { remaining_budget };
+        let min_capacity = self.config.chunk_capacity(min_capacity, room);
 
-    /// Allocate a slice populated by default-valued elements.
-    pub fn alloc_slice_default<T: Copy+Default>(&mut self, len: usize)-> &'a mut [T] {
-        self.alloc_slice_fn(len, |_| Default::default())
+        if self.head.data.is_empty() {
+            self.check_tripwire(min_capacity - free);
+            let old_capacity = self.head.data.capacity();
+            let old_start = self.head.data.as_ptr() as usize;
+            // Fill the new buffer like a fresh chunk's.
+            self.head.data.reserve_exact(min_capacity);
+            let new_capacity = self.head.data.capacity();
+            self.head.data.resize(new_capacity, CHUNK_FILL);
+            self.head.data.clear();
+            self.total_capacity = self.total_capacity - old_capacity + self.head.data.capacity();
+            // Marks recognize the chunk by its buffer, which just moved.
+            let new_start = self.head.data.as_ptr() as usize;
+            for mark in self.marks.iter_mut().chain(self.template.as_mut()) {
+                if mark.chunk == old_start {
+                    mark.chunk = new_start;
+                }
+            }
+        } else if self.config.grow_mode == GrowMode::Realloc {
+            self.realloc_head(min_capacity);
+        } else {
+            self.add_chunk(min_capacity);
+        }
     }
-}
 
+    /// Move the current chunk's contents into a new buffer with room for
+    /// `additional` more bytes, keeping every allocation as aligned as it
+    /// was.
+    fn realloc_head(&mut self, additional: usize) {
+        let len = self.head.data.len();
+        let old_capacity = self.head.data.capacity();
+        let align = self.max_align;
+        let capacity = len.checked_add(additional)
+            .and_then(|capacity| capacity.checked_add(align - 1))
+            .unwrap_or_else(|| panic!("{}", AllocError::BudgetExceeded));
+        if capacity - old_capacity > self.config.max_total_bytes.saturating_sub(self.total_capacity) {
+            panic!("{}", AllocError::BudgetExceeded);
+        }
+        self.check_tripwire(capacity - old_capacity);
 
-#[test]
-fn construct_simple() {
-    let mut arena = Arena::with_capacity(4);
-    let mut allocator = arena.allocator();
-
-    let x: &mut i32 = allocator.alloc(44);
-    let y: &mut u8 = allocator.alloc(3);
-    let z: &mut u32 = allocator.alloc(0x11223344);
-    let w: &mut f64 = allocator.alloc_default();
-    assert_eq!(*x, 44);
-    assert_eq!(*y, 3);
-    assert_eq!(*z, 0x11223344);
-    assert_eq!(*w, 0.0);
-}
+        let mut data: Vec<u8> = ChunkStorage::with_capacity(capacity);
+        let old_start = self.head.data.as_ptr() as usize;
+        // Start the contents at the same offset from a multiple of the
+        // largest alignment ever used as before.
+        let lead = padding_for((data.as_ptr() as usize).wrapping_sub(old_start), align);
+        data.extend(iter::repeat_n(CHUNK_FILL, lead));
+        data.extend_from_slice(self.head.data.filled());
+        let moved_by = (data.as_ptr() as usize + lead).wrapping_sub(old_start);
 
-#[test]
-fn just_enough_capacity() {
-    let mut arena = Arena::with_capacity(7);
-    arena.allocator().alloc_slice_default::<u8>(7);
+        for entry in self.drops.iter_mut().chain(self.arena_drops.iter_mut().map(|e| &mut e.entry)) {
+            let address = entry.ptr as usize;
+            if address >= old_start && address <= old_start + len {
+                entry.ptr = address.wrapping_add(moved_by) as *mut u8;
+            }
+        }
+        #[cfg(feature = "debug-track")]
+        for entry in &mut self.boundaries {
+            if entry.0 >= old_start && entry.0 <= old_start + len {
+                entry.0 = entry.0.wrapping_add(moved_by);
+            }
+        }
+        for mark in self.marks.iter_mut().chain(self.template.as_mut()) {
+            if mark.chunk == old_start {
+                mark.chunk = data.as_ptr() as usize;
+                mark.len += lead;
+            }
+        }
+        // The filler bytes in front count as allocated.
+        self.padding_bytes += lead;
+        self.total_capacity = self.total_capacity - old_capacity + data.capacity();
+        self.head.data = data;
+    }
 
-    assert!(arena.head.next.is_none());
-    assert_eq!(arena.head.data.len(), 7);
+    /// Allocate from this arena through a `BrandedArena`, whose handles
+    /// carry a brand unique to this call.
+    ///
+    /// A `BrandedRef` from one call can't be used with the `BrandedArena`
+    /// of another, and can't escape the closure, so mixing up values from
+    /// different arenas is a compile error:
+    ///
+    /// ```compile_fail
+    /// use copy_arena::Arena;
+    ///
+    /// let mut first = Arena::new();
+    /// let mut second = Arena::new();
+    /// first.branded(|mut a| {
+    ///     let r = a.alloc(1u32);
+    ///     second.branded(|b| {
+    ///         b.get(r);
+    ///     });
+    /// });
+    /// ```
+    pub fn branded<R, F>(&mut self, f: F) -> R
+        where F: for<'id> FnOnce(BrandedArena<'id>) -> R
+    {
+        f(BrandedArena::new(self.allocator()))
+    }
 }
 
-#[test]
-fn barely_too_little_capacity() {
-    let mut arena = Arena::with_capacity(7);
-    arena.allocator().alloc_slice_default::<u8>(8);
-
-    assert!(arena.head.next.is_some());
-    assert_eq!(arena.head.data.len(), 8);
-}
+impl<S: ChunkStorage> Arena<S> {
+    /// Construct a new Arena configured by `config`, whose chunks are kept
+    /// in `S`.
+    pub fn with_storage(config: ArenaConfig) -> Arena<S> {
+        assert!(config.granularity.is_power_of_two(), "Arena granularity must be a power of two");
+        let initial_capacity = if config.eager {
+            config.chunk_capacity(config.initial_capacity, config.max_total_bytes)
+        } else {
+            0
+        };
+        let head: Chunk<S> = Chunk::new(initial_capacity);
+        Arena {
+            config,
+            total_capacity: head.data.capacity(),
+            head,
+            next_index: 0,
+            emitted: 0,
+            sequence: 0,
+            id: fresh_id(),
+            grew: false,
+            first_growth_at: None,
+            frozen: false,
+            tripwire: usize::MAX,
+            padding_bytes: 0,
+            allocation_count: 0,
+            max_align: 1,
+            peak_bytes: 0,
+            peak_chunks: 1,
+            drops: Vec::new(),
+            arena_drops: Vec::new(),
+            kept: Vec::new(),
+            marks: Vec::new(),
+            template: None,
+            generation: None,
+            absorbed_generations: Vec::new(),
+            trace: None,
+            #[cfg(feature = "debug-track")]
+            report: Vec::new(),
+            #[cfg(feature = "debug-track")]
+            boundaries: Vec::new(),
+        }
+    }
 
-#[test]
-fn many_u8s() {
-    let mut arena = Arena::with_capacity(52);
-    for i in 0..100 {
-        assert_eq!(*arena.allocator().alloc(4), 4u8);
-        assert_eq!(arena.head.data.len(), (i % 52) + 1);
+    /// Call `f` after every allocation with its size, its alignment, and the
+    /// offset in its chunk it was placed at, e.g. to trace allocation
+    /// activity. Replaces any earlier callback.
+    pub fn set_trace<F: FnMut(usize, usize, usize) + Send + 'static>(&mut self, f: F) {
+        self.trace = Some(Trace(Box::new(f)));
     }
-    assert!(arena.head.data.capacity() > 52);
-}
 
+    /// Get the configuration this arena was constructed with, to inspect
+    /// or to construct another arena like it.
+    pub fn config(&self) -> ArenaConfig {
+        self.config
+    }
 
-#[test]
-fn zero_capacity() {
-    let mut arena = Arena::with_capacity(0);
-    assert_eq!(arena.head.data.capacity(), 0);
+    /// Add a chunk that can hold an allocation of `size` bytes aligned to
+    /// `align`.
+    fn grow(&mut self, size: usize, align: usize) -> Result<(), AllocError> {
+        self.grow_by(size, align, true)
+    }
 
-    assert_eq!(*arena.allocator().alloc(9u32), 9u32);
+    /// Add a chunk that can hold an allocation of `size` bytes aligned to
+    /// `align`: a larger one if `doubling`, or otherwise one just big
+    /// enough.
+    fn grow_by(&mut self, size: usize, align: usize, doubling: bool) -> Result<(), AllocError> {
+        if self.frozen {
+            return Err(AllocError::Frozen);
+        }
+        // A chunk of `needed` bytes holds the allocation however its buffer
+        // happens to be aligned. Nothing bigger than isize::MAX can be
+        // allocated at all, so no budget could hold it.
+        let needed = match size.checked_add(align - 1) {
+            Some(needed) if needed <= isize::MAX as usize => needed,
+            _ => { return Err(AllocError::BudgetExceeded); }
+        };
+        // Double the current chunk (or the asked for allocation), but never
+        // go below `needed`, so the caller's retry is sure to succeed.
+        let new_chunk_size = if self.first_chunk_deferred() {
+            cmp::max(self.config.initial_capacity, needed)
+        } else if doubling {
+            let doubled = cmp::max(self.head.data.len(), needed).saturating_mul(2);
+            cmp::max(cmp::min(doubled, isize::MAX as usize), needed)
+        } else {
+            needed
+        };
 
-    assert!(arena.head.data.capacity()>=4);
-    assert!(arena.head.data.len()==4);
-}
+        // Settle for a smaller chunk if that is all the budget allows.
+        let remaining_budget = self.config.max_total_bytes.saturating_sub(self.total_capacity);
+        if needed > remaining_budget {
+            return Err(AllocError::BudgetExceeded);
+        }
+        let new_chunk_size = cmp::min(new_chunk_size, remaining_budget);
+        self.add_chunk(self.config.chunk_capacity(new_chunk_size, remaining_budget));
+        Ok(())
+    }
 
-#[test]
-fn surprisingly_large() {
-    // Make sure asking for an allocation that is larger than the
-    // block we otherwise would have allocated works fine.
-    let mut arena = Arena::with_capacity(10);
+    /// The granularity every allocation's size and alignment are rounded
+    /// up to.
+    pub fn granularity(&self) -> usize {
+        self.config.granularity
+    }
 
-    {
-        let mut allocator = arena.allocator();
-        let x = allocator.alloc(7u32);
-        let ys : &[u8] = allocator.alloc_slice_default(600);
+    /// Panic whenever growing would take the arena's total capacity past
+    /// `max_total_bytes`, e.g. so a fuzz target fails loudly on inputs that
+    /// make it use too much memory instead of running the fuzzer out of it.
+    ///
+    /// Unlike a budget, this is not an error to handle: even the `try_`
+    /// methods panic.
+    pub fn set_panic_on_growth_over(&mut self, max_total_bytes: usize) {
+        self.tripwire = max_total_bytes;
+    }
 
-        assert_eq!(*x, 7u32);
-        assert_eq!(ys.len(), 600);
+    fn check_tripwire(&self, extra: usize) {
+        if extra > self.tripwire.saturating_sub(self.total_capacity) {
+            panic!("Arena would grow past {} bytes, the limit set by set_panic_on_growth_over",
+                   self.tripwire);
+        }
     }
 
-    assert!(arena.head.next.is_some());
-    assert!(arena.head.data.capacity() >= 600);
-    assert_eq!(arena.head.data.len(), 600);
-}
+    /// Whether the arena was constructed without its first chunk and
+    /// hasn't allocated it yet.
+    fn first_chunk_deferred(&self) -> bool {
+        !self.config.eager && self.total_capacity == 0
+    }
 
-#[test]
-fn construct_slices() {
-    let mut arena = Arena::with_capacity(4);
-    let mut allocator = arena.allocator();
+    fn add_chunk(&mut self, chunk_size: usize) {
+        self.check_tripwire(chunk_size);
+        let mut new_head: Chunk<S> = Chunk::new(chunk_size);
+        if self.first_chunk_deferred() {
+            // Take the empty placeholder head's place rather than chaining
+            // behind it.
+            let old_start = self.head.data.as_ptr() as usize;
+            let new_start = new_head.data.as_ptr() as usize;
+            for mark in self.marks.iter_mut().chain(self.template.as_mut()) {
+                if mark.chunk == old_start {
+                    mark.chunk = new_start;
+                }
+            }
+            self.total_capacity = new_head.data.capacity();
+            self.head = new_head;
+            return;
+        }
+        new_head.base_offset = self.head.base_offset + self.head.data.len();
 
-    let s = ::std::str::from_utf8(allocator.alloc_slice(b"abc")).unwrap();
-    let xs: &[i32] = allocator.alloc_slice_fn(10, |idx| (idx as i32)*7);
-    let ys: &[u64] = allocator.alloc_slice_default(4);
+        self.total_capacity += new_head.data.capacity();
+        if !self.grew {
+            self.first_growth_at = Some(self.allocation_count);
+        }
+        self.grew = true;
+        mem::swap(&mut self.head, &mut new_head);
+        self.head.next = Some(Box::new(new_head));
+    }
 
-    assert_eq!(xs[9], 9*7);
-    assert_eq!(s, "abc");
-    assert_eq!(ys[0], 0);
-}
+    /// Construct an Allocator for this arena.
+    pub fn allocator(&mut self) -> Allocator<'_, S> {
+        Allocator {
+            arena: self
+        }
+    }
 
-#[test]
-fn zero_size() {
-    let mut arena = Arena::with_capacity(4);
+    /// Run `f` with only shared access to the arena, for a phase that
+    /// should read what was allocated but never allocate more.
+    ///
+    /// An `Allocator` needs `&mut Arena`, so `f` can't get one, and none
+    /// can be alive outside it either, so allocating in the scope is a
+    /// compile error:
+    ///
+    /// ```compile_fail
+    /// use copy_arena::Arena;
+    ///
+    /// let mut arena = Arena::new();
+    /// arena.read_only_scope(|arena| {
+    ///     arena.allocator().alloc(1u32);
+    /// });
+    /// ```
+    pub fn read_only_scope<R, F>(&mut self, f: F) -> R
+        where F: FnOnce(&Arena<S>) -> R
     {
-        let mut allocator = arena.allocator();
-
-        let many_units = allocator.alloc_slice_fn(500, |_| () );
-        assert_eq!(many_units.len(), 500);
+        f(self)
+    }
+
+    /// Discard everything allocated in the arena, so its memory can be
+    /// reused.
+    ///
+    /// Only the most recent (and largest) chunk is kept; the rest are
+    /// released. Destructors registered by `Allocator::alloc_fn` and
+    /// `alloc_reset_dropped` run now, but values from `alloc_arena_dropped`
+    /// are moved onto the heap and only dropped along with the arena.
+    ///
+    /// If `freeze_template()` was called, everything allocated before it is
+    /// kept instead, along with the chunks holding it, and only what came
+    /// after is discarded.
+    pub fn reset(&mut self) {
+        self.note_peak();
+        if let Some(template) = self.template {
+            self.rewind(template);
+            self.bump_generation();
+            self.marks.clear();
+            return;
+        }
+        self.discard_drops(0, 0);
+        self.bump_generation();
+        self.marks.clear();
+        #[cfg(feature = "debug-track")]
+        self.boundaries.clear();
+        self.head.next = None;
+        self.head.data.clear();
+        self.head.base_offset = 0;
+        self.total_capacity = self.head.data.capacity();
+    }
+
+    /// Discard everything allocated in the arena like `reset()`, but keep
+    /// chunks, newest first, only while their capacities add up to at most
+    /// `max_keep_bytes`, so after a burst the arena settles back toward
+    /// that size. Chunks that would take it over are released.
+    ///
+    /// The largest kept chunk becomes the one allocations fill first; the
+    /// rest stay behind it, empty, like `with_chunks()`'s. If every chunk
+    /// is larger than `max_keep_bytes`, the smallest one is kept. With a
+    /// template set by `freeze_template()`, this is the same as `reset()`.
+    pub fn reset_keeping(&mut self, max_keep_bytes: usize) {
+        if self.template.is_some() {
+            return self.reset();
+        }
+        self.note_peak();
+        self.discard_drops(0, 0);
+        self.bump_generation();
+        self.marks.clear();
+        #[cfg(feature = "debug-track")]
+        self.boundaries.clear();
+        let mut kept: Vec<Chunk<S>> = Vec::new();
+        let mut kept_bytes = 0;
+        let mut smallest: Option<Chunk<S>> = None;
+        let mut next = Some(Box::new(mem::replace(&mut self.head, Chunk::new(0))));
+        while let Some(mut chunk) = next {
+            next = chunk.next.take();
+            chunk.data.clear();
+            chunk.base_offset = 0;
+            let capacity = chunk.data.capacity();
+            if capacity <= max_keep_bytes - kept_bytes {
+                kept_bytes += capacity;
+                kept.push(*chunk);
+            } else if smallest.as_ref().is_none_or(|small| capacity < small.data.capacity()) {
+                smallest = Some(*chunk);
+            }
+        }
+        if kept.is_empty() {
+            let chunk = smallest.expect("Arena always has a chunk");
+            kept_bytes = chunk.data.capacity();
+            kept.push(chunk);
+        }
+
+        let mut largest = 0;
+        for (index, chunk) in kept.iter().enumerate() {
+            if chunk.data.capacity() > kept[largest].data.capacity() {
+                largest = index;
+            }
+        }
+        let mut head = kept.remove(largest);
+        for mut chunk in kept.into_iter().rev() {
+            chunk.next = head.next.take();
+            head.next = Some(Box::new(chunk));
+        }
+        self.total_capacity = kept_bytes;
+        self.head = head;
+    }
+
+    /// Keep everything allocated so far across resets: from now on,
+    /// `reset()` truncates the arena back to this point rather than
+    /// emptying it, e.g. so data present at the start of every frame
+    /// doesn't have to be rebuilt each time.
+    ///
+    /// Calling this again moves the template's end to the current point.
+    /// Any marks from `Allocator::push_mark()` are forgotten, since popping
+    /// one would discard part of the template.
+    pub fn freeze_template(&mut self) {
+        self.marks.clear();
+        self.template = Some(Mark {
+            chunk: self.head.data.as_ptr() as usize,
+            len: self.head.data.len(),
+            drops: self.drops.len(),
+            arena_drops: self.arena_drops.len(),
+        });
+    }
+
+    /// Forget the template set by `freeze_template()`, so the next
+    /// `reset()` empties the arena again.
+    pub fn clear_template(&mut self) {
+        self.template = None;
+    }
+
+    /// Discard everything allocated since `mark`, releasing the chunks
+    /// added since.
+    fn rewind(&mut self, mark: Mark) {
+        self.discard_drops(mark.drops, mark.arena_drops);
+        while self.head.data.as_ptr() as usize != mark.chunk {
+            let older = self.head.next.take().expect("Arena mark's chunk is gone");
+            self.total_capacity -= self.head.data.capacity();
+            self.head = *older;
+        }
+        unsafe { self.head.data.set_len(mark.len); }
+        #[cfg(feature = "debug-track")]
+        self.prune_boundaries();
+    }
+
+    /// Whether `id` is this arena's id, as tagged onto allocations by
+    /// `Allocator::alloc_tagged()`, e.g. to assert that a reference handed
+    /// to a structure built in this arena came from it too.
+    ///
+    /// Ids are picked at random, so one from another arena matches only by
+    /// (astronomically unlikely) chance. A `deep_clone()` gets an id of its
+    /// own.
+    pub fn owns_id(&self, id: u64) -> bool {
+        self.id == id
+    }
+
+    /// Start `Allocator::alloc_numbered`'s sequence numbers over from zero.
+    pub fn reset_sequence(&mut self) {
+        self.sequence = 0;
+    }
+
+    /// Get a reference to the value allocated at `offset`.
+    ///
+    /// Panics if `offset` is not inside the arena's allocated memory, or if
+    /// it is not suitably aligned for `T`.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must have come from allocating a `T` in this arena (e.g. by
+    /// `Allocator::alloc_offset`), and the arena must not have been reset
+    /// since.
+    pub unsafe fn resolve<T>(&self, offset: RelOffset) -> &T {
+        &*(self.logical_ptr(offset, mem::size_of::<T>(), mem::align_of::<T>()) as *const T)
+    }
+
+    fn logical_ptr(&self, offset: RelOffset, size: usize, align: usize) -> *const u8 {
+        let offset = offset.0;
+        let mut iter: &Chunk<S> = &self.head;
+        loop {
+            let base = iter.base_offset as u64;
+            if offset >= base && offset - base + size as u64 <= iter.data.len() as u64 {
+                let ptr = unsafe { iter.data.as_ptr().add((offset - base) as usize) };
+                assert_eq!(padding_for(ptr as usize, align), 0, "Arena offset is misaligned");
+                return ptr;
+            }
+            match iter.next {
+                None => { panic!("Arena offset out of range"); }
+                Some(ref next) => { iter = next; }
+            }
+        }
+    }
+
+    /// Copy the graph of `T`s reachable from `roots` into a compact blob,
+    /// leaving out everything unreachable.
+    ///
+    /// Nodes are laid out back to back as a `[T]`, roots first and in
+    /// order, then the rest as they are discovered. Every link visited by
+    /// `Traverse::visit_links` is rewritten to the node's offset in the
+    /// blob, so `Arena::from_vec(blob)` resolves them again; with distinct
+    /// roots, `roots[i]` ends up at `RelOffset(i * size_of::<T>())`.
+    ///
+    /// # Safety
+    ///
+    /// Every root, and every link reachable from them, must be the offset
+    /// of a `T` allocated in this arena, as for `resolve()`.
+    pub unsafe fn serialize_graph<T: Traverse>(&self, roots: &[RelOffset]) -> Vec<u8> {
+        let size = mem::size_of::<T>();
+        let mut order: Vec<RelOffset> = Vec::new();
+        let mut new_index: HashMap<RelOffset, usize> = HashMap::new();
+        for &root in roots {
+            new_index.entry(root).or_insert_with(|| {
+                order.push(root);
+                order.len() - 1
+            });
+        }
+
+        let mut nodes: Vec<T> = Vec::new();
+        while nodes.len() < order.len() {
+            let mut node = *self.resolve::<T>(order[nodes.len()]);
+            node.visit_links(|link| {
+                let index = *new_index.entry(*link).or_insert_with(|| {
+                    order.push(*link);
+                    order.len() - 1
+                });
+                *link = RelOffset((index * size) as u64);
+            });
+            nodes.push(node);
+        }
+
+        let mut blob = vec![0u8; nodes.len() * size];
+        ptr::copy_nonoverlapping(nodes.as_ptr() as *const u8, blob.as_mut_ptr(), blob.len());
+        blob
+    }
+
+    /// List the callsite and size of every `alloc` and `alloc_slice` call
+    /// made on this arena, in order. Only available with the
+    /// `debug-track` feature.
+    #[cfg(feature = "debug-track")]
+    pub fn allocation_report(&self) -> &[(Location<'static>, usize)] {
+        &self.report
+    }
+
+    /// Iterate over the bytes of each allocation still in the arena, in the
+    /// order they were made, e.g. to check for overlaps or dump the arena's
+    /// contents. Alignment padding between allocations is left out. Only
+    /// available with the `debug-track` feature.
+    #[cfg(feature = "debug-track")]
+    pub fn allocations(&self) -> impl Iterator<Item=&[u8]> + '_ {
+        self.boundaries.iter().map(|&(address, len)| unsafe {
+            slice::from_raw_parts(address as *const u8, len)
+        })
+    }
+
+    /// Change the recorded length of the allocation at `address`, if it is
+    /// the most recent one, after it was resized in place.
+    #[cfg(feature = "debug-track")]
+    fn set_last_boundary(&mut self, address: usize, len: usize) {
+        if let Some(last) = self.boundaries.last_mut() {
+            if last.0 == address {
+                last.1 = len;
+            }
+        }
+    }
+
+    /// Forget the recorded allocations that are no longer within the filled
+    /// part of any chunk.
+    #[cfg(feature = "debug-track")]
+    fn prune_boundaries(&mut self) {
+        let mut filled = Vec::new();
+        let mut chunk = Some(&self.head);
+        while let Some(current) = chunk {
+            let start = current.data.as_ptr() as usize;
+            filled.push((start, start + current.data.len()));
+            chunk = current.next.as_deref();
+        }
+        self.boundaries.retain(|&(address, len)| {
+            filled.iter().any(|&(start, end)| address >= start && address + len <= end)
+        });
+    }
+
+    /// Run and forget the registered destructors, newest first within
+    /// each list, the reset-scoped ones before the arena-scoped ones.
+    fn run_drops(&mut self) {
+        while let Some(entry) = self.drops.pop() {
+            unsafe { (entry.drop)(entry.ptr); }
+        }
+        while let Some(ArenaDropEntry { entry, .. }) = self.arena_drops.pop() {
+            unsafe { (entry.drop)(entry.ptr); }
+        }
+    }
+
+    /// Get every value registered after the first `drop_count` reset-scoped
+    /// and `arena_drop_count` arena-scoped ones out of the chunks, newest
+    /// first, since their memory is about to be reused: reset-scoped values
+    /// are dropped, and arena-scoped ones are moved to the heap.
+    fn discard_drops(&mut self, drop_count: usize, arena_drop_count: usize) {
+        while self.drops.len() > drop_count {
+            let entry = self.drops.pop().unwrap();
+            unsafe { (entry.drop)(entry.ptr); }
+        }
+        while self.arena_drops.len() > arena_drop_count {
+            let ArenaDropEntry { entry, keep } = self.arena_drops.pop().unwrap();
+            self.kept.push(unsafe { keep(entry.ptr) });
+        }
+    }
+
+    /// Get the number of destructors waiting to run, e.g. to check in a
+    /// test that nothing was left behind. This counts values still in the
+    /// chunks and those from `Allocator::alloc_arena_dropped` that a reset
+    /// moved aside.
+    pub fn registered_drop_count(&self) -> usize {
+        self.drops.len() + self.arena_drops.len() + self.kept.len()
+    }
+
+    fn bump_generation(&mut self) {
+        if let Some(ref generation) = self.generation {
+            generation.fetch_add(1, Ordering::Relaxed);
+        }
+        // Every guard from an absorbed arena is stale now, so its
+        // generation needn't be bumped again.
+        for generation in self.absorbed_generations.drain(..) {
+            generation.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether the arena has ever needed more than its initial chunk.
+    ///
+    /// This stays true after a `reset()`, even though the extra chunks are
+    /// gone, so it reliably tells whether the initial capacity was enough.
+    pub fn has_grown(&self) -> bool {
+        self.grew
+    }
+
+    /// Get how many allocations had been made when the arena first needed
+    /// another chunk, or `None` if it never has. A count well short of a
+    /// typical workload's says `with_capacity` was too small.
+    ///
+    /// Like `has_grown()`, this survives a `reset()`.
+    pub fn allocations_before_first_growth(&self) -> Option<usize> {
+        self.first_growth_at
+    }
+
+    /// Get the number of bytes skipped over to align allocations, in total
+    /// since the arena was constructed.
+    ///
+    /// A high count suggests allocating in order of descending alignment.
+    pub fn padding_bytes(&self) -> usize {
+        self.padding_bytes
+    }
+
+    /// Get the number of bytes allocated since the arena was constructed or
+    /// last reset, including alignment padding but not the unused ends of
+    /// full chunks.
+    pub fn bytes_used(&self) -> usize {
+        self.head.base_offset + self.head.data.len()
+    }
+
+    /// Get the most bytes the arena has had allocated at once, as
+    /// `bytes_used()` would have reported it, since it was constructed.
+    pub fn peak_bytes_used(&self) -> usize {
+        cmp::max(self.peak_bytes, self.bytes_used())
+    }
+
+    /// Record the current usage in the peaks, before some of it is given
+    /// back.
+    fn note_peak(&mut self) {
+        self.peak_bytes = self.peak_bytes_used();
+        self.peak_chunks = cmp::max(self.peak_chunks, self.chunk_count());
+    }
+
+    /// Suggest how to configure an arena for a workload like the one this
+    /// arena has seen so far, from its peak usage and padding.
+    pub fn tuning_hint(&self) -> TuningHint {
+        let peak = self.peak_bytes_used();
+        let suggested_capacity = if peak == 0 {
+            self.config.initial_capacity
+        } else {
+            peak.checked_next_power_of_two().unwrap_or(peak)
+        };
+        let peak_chunk_count = cmp::max(self.peak_chunks, self.chunk_count());
+        TuningHint {
+            suggested_capacity,
+            reserve_up_front: peak_chunk_count > 1,
+            peak_chunk_count,
+            padding_bytes: self.padding_bytes,
+        }
+    }
+
+    /// Whether nothing has been allocated in the arena since it was
+    /// constructed or last reset. Allocations of zero bytes don't count.
+    pub fn is_empty(&self) -> bool {
+        self.bytes_used() == 0
+    }
+
+    /// Get the number of chunks the arena holds.
+    pub fn chunk_count(&self) -> usize {
+        let mut count = 1;
+        let mut iter: &Chunk<S> = &self.head;
+        while let Some(ref next) = iter.next {
+            count += 1;
+            iter = next;
+        }
+        count
+    }
+
+    /// Get how full each chunk is, as its filled bytes over its capacity,
+    /// in allocation order. A low ratio on an older chunk means space was
+    /// left behind when an allocation too big for it forced a new chunk.
+    ///
+    /// A chunk with no capacity at all counts as full.
+    pub fn chunk_fill_ratios(&self) -> Vec<f32> {
+        let mut ratios = Vec::new();
+        let mut iter: &Chunk<S> = &self.head;
+        loop {
+            let capacity = iter.data.capacity();
+            ratios.push(if capacity == 0 { 1.0 } else { iter.data.len() as f32 / capacity as f32 });
+            match iter.next {
+                None => { break; }
+                Some(ref next) => { iter = next; }
+            }
+        }
+        ratios.reverse();
+        ratios
+    }
+
+    /// Estimate the memory the chunk list's bookkeeping takes on top of the
+    /// chunks' buffers: each chunk's header, plus a word of heap allocator
+    /// overhead for each chunk boxed behind the head.
+    pub fn overhead_bytes(&self) -> usize {
+        let chunks = self.chunk_count();
+        chunks * mem::size_of::<Chunk<S>>() + (chunks - 1) * mem::size_of::<usize>()
+    }
+
+    /// Get the number of allocations made in the arena, in total since it
+    /// was constructed.
+    pub fn allocation_count(&self) -> usize {
+        self.allocation_count
+    }
+
+    /// Copy each of `roots`, which must have been allocated in this arena,
+    /// into `dest`, returning each root's old address paired with its copy,
+    /// in order, so the caller can fix up references to it before this
+    /// arena is reset or dropped.
+    ///
+    /// A root listed more than once is only copied once. Only the roots
+    /// themselves are copied: references inside them still point into this
+    /// arena until they are fixed up.
+    pub fn copy_into<'d, T: Copy + 'd, D: ChunkStorage>(&self, dest: &mut Allocator<'d, D>,
+                                                     roots: &[&T]) -> Vec<(*const T, *mut T)>
+    {
+        let mut copies: HashMap<*const T, *mut T> = HashMap::new();
+        roots.iter().map(|&root| {
+            let old = root as *const T;
+            debug_assert!(self.contains(old) || mem::size_of::<T>() == 0,
+                          "Arena copy_into root is not in the arena");
+            let new = *copies.entry(old).or_insert_with(|| dest.alloc(*root) as *mut T);
+            (old, new)
+        }).collect()
+    }
+
+    /// Make an independent copy of the arena, with the same chunks holding
+    /// the same bytes.
+    ///
+    /// References into this arena still point into this arena, not the
+    /// copy; only offset-based access (`RelOffset`, `resolve()`) carries
+    /// over. The copy has no trace callback or template. Panics if the
+    /// arena holds values that need dropping, since those can't be copied
+    /// bytewise.
+    pub fn deep_clone(&self) -> Arena<S> {
+        assert!(self.drops.is_empty() && self.arena_drops.is_empty(),
+                "Arena holding values that need dropping can't be cloned");
+        let mut sources: Vec<&Chunk<S>> = Vec::new();
+        let mut iter: &Chunk<S> = &self.head;
+        loop {
+            sources.push(iter);
+            match iter.next {
+                None => { break; }
+                Some(ref next) => { iter = next; }
+            }
+        }
+
+        // Rebuild the list oldest first, so each copy links to the one
+        // before it.
+        let mut list: Option<Box<Chunk<S>>> = None;
+        let mut total_capacity = 0;
+        // Where each copy's bytes start, oldest first.
+        #[cfg(feature = "debug-track")]
+        let mut copies = Vec::new();
+        for source in sources.iter().rev() {
+            let mut chunk: Chunk<S> = Chunk::new(source.data.capacity());
+            let len = source.data.len();
+            unsafe {
+                ptr::copy_nonoverlapping(source.data.as_ptr(), chunk.data.as_mut_ptr(), len);
+                chunk.data.set_len(len);
+            }
+            chunk.base_offset = source.base_offset;
+            #[cfg(feature = "debug-track")]
+            copies.push(chunk.data.as_ptr() as usize);
+            chunk.next = list;
+            total_capacity += chunk.data.capacity();
+            list = Some(Box::new(chunk));
+        }
+
+        Arena {
+            config: self.config,
+            head: *list.expect("Arena always has a chunk"),
+            total_capacity,
+            next_index: self.next_index,
+            emitted: self.emitted,
+            sequence: self.sequence,
+            id: fresh_id(),
+            grew: self.grew,
+            first_growth_at: self.first_growth_at,
+            frozen: self.frozen,
+            tripwire: self.tripwire,
+            padding_bytes: self.padding_bytes,
+            allocation_count: self.allocation_count,
+            max_align: self.max_align,
+            peak_bytes: self.peak_bytes,
+            peak_chunks: self.peak_chunks,
+            drops: Vec::new(),
+            arena_drops: Vec::new(),
+            kept: Vec::new(),
+            marks: Vec::new(),
+            template: None,
+            generation: None,
+            absorbed_generations: Vec::new(),
+            trace: None,
+            #[cfg(feature = "debug-track")]
+            report: self.report.clone(),
+            #[cfg(feature = "debug-track")]
+            boundaries: self.boundaries.iter().filter_map(|&(address, len)| {
+                sources.iter().zip(copies.iter().rev()).find_map(|(source, &copy)| {
+                    let start = source.data.as_ptr() as usize;
+                    if address >= start && address + len <= start + source.data.len() {
+                        Some((copy + (address - start), len))
+                    } else {
+                        None
+                    }
+                })
+            }).collect(),
+        }
+    }
+
+    /// Take over everything allocated in `other`, along with its memory.
+    ///
+    /// References into either arena stay valid, now tied to this one, and
+    /// `other`'s registered destructors run when this arena is reset or
+    /// dropped. Allocation carries on in this arena's current chunk. The
+    /// logical offsets of `other`'s allocations and of ones in this arena's
+    /// current chunk change, so `RelOffset`s into them don't carry over.
+    /// Guards from `other`'s `Allocator::alloc_guarded()` stay fresh until
+    /// this arena is reset or dropped.
+    pub fn absorb(&mut self, mut other: Arena<S>) {
+        self.drops.append(&mut other.drops);
+        self.arena_drops.append(&mut other.arena_drops);
+        self.kept.append(&mut other.kept);
+        self.absorbed_generations.extend(other.generation.take());
+        self.absorbed_generations.append(&mut other.absorbed_generations);
+        self.max_align = cmp::max(self.max_align, other.max_align);
+        self.total_capacity += other.total_capacity;
+        self.padding_bytes += other.padding_bytes;
+        if !self.grew {
+            self.first_growth_at = Some(self.allocation_count);
+        }
+        self.allocation_count += other.allocation_count;
+        self.grew = true;
+
+        // Slot other's chunks in just behind the head, as though they had
+        // been allocated before it.
+        let mut theirs = mem::replace(&mut other.head, Chunk::new(0));
+        {
+            let mut last: &mut Chunk<S> = &mut theirs;
+            while last.next.is_some() {
+                last = last.next.as_mut().unwrap();
+            }
+            last.next = self.head.next.take();
+        }
+        self.head.next = Some(Box::new(theirs));
+        self.renumber();
+    }
+
+    /// Recompute every chunk's logical offset from the lengths of the ones
+    /// before it.
+    fn renumber(&mut self) {
+        let mut lens = Vec::new();
+        let mut iter: &Chunk<S> = &self.head;
+        loop {
+            lens.push(iter.data.len());
+            match iter.next {
+                None => { break; }
+                Some(ref next) => { iter = next; }
+            }
+        }
+        let mut bases = vec![0; lens.len()];
+        for index in (0..lens.len() - 1).rev() {
+            bases[index] = bases[index + 1] + lens[index + 1];
+        }
+
+        let mut iter: Option<&mut Chunk<S>> = Some(&mut self.head);
+        let mut index = 0;
+        while let Some(chunk) = iter {
+            chunk.base_offset = bases[index];
+            index += 1;
+            iter = chunk.next.as_deref_mut();
+        }
+    }
+
+    /// Record the arena's current usage, to compare with a later snapshot.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            bytes_used: self.bytes_used(),
+            chunk_count: self.chunk_count(),
+            allocation_count: self.allocation_count(),
+        }
+    }
+
+    /// Gather the arena's statistics into one struct, e.g. to export to a
+    /// metrics system.
+    pub fn metrics(&self) -> ArenaMetrics {
+        ArenaMetrics {
+            capacity: self.capacity(),
+            bytes_used: self.bytes_used(),
+            chunk_count: self.chunk_count(),
+            allocation_count: self.allocation_count(),
+            peak_bytes: self.peak_bytes_used(),
+            padding_bytes: self.padding_bytes(),
+            has_grown: self.has_grown(),
+            allocations_before_first_growth: self.allocations_before_first_growth(),
+        }
+    }
+
+    /// Get the number of bytes of memory that have been allocated
+    /// in service of this arena. Not all of this capacity is necessarily
+    /// useful, since asked-for memory may not perfectly fit in the
+    /// underlying blocks allocated.
+    pub fn capacity(&self) -> usize {
+        self.total_capacity
+    }
+
+    /// Get the number of bytes reserved by the arena's chunks, used or not.
+    /// The same as `capacity()`.
+    pub fn reserved_bytes(&self) -> usize {
+        self.capacity()
+    }
+
+    /// Get the number of bytes reserved by the arena's chunks but not
+    /// allocated, summed over every chunk.
+    ///
+    /// Only the current chunk's share can still be allocated; the rest is
+    /// stranded at the ends of older chunks until the next reset.
+    pub fn total_unused(&self) -> usize {
+        let mut unused = 0;
+        let mut iter: &Chunk<S> = &self.head;
+        loop {
+            unused += iter.data.capacity() - iter.data.len();
+            match iter.next {
+                None => { return unused; }
+                Some(ref next) => { iter = next; }
+            }
+        }
+    }
+
+    /// Whether `ptr` points into memory this arena has allocated.
+    ///
+    /// Only the allocated part of each chunk counts, so a pointer one past
+    /// the end of the last allocation, or into a chunk's unused tail, isn't
+    /// contained.
+    pub fn contains<T>(&self, ptr: *const T) -> bool {
+        let address = ptr as usize;
+        self.chunks().any(|chunk| {
+            let start = chunk.as_ptr() as usize;
+            address >= start && address < start + chunk.len()
+        })
+    }
+
+    /// Get the signed distance in bytes from `from` to `to`, e.g. to encode
+    /// a relative link between two allocations.
+    ///
+    /// Returns `None` unless both point into the allocated part of the same
+    /// chunk, since the distance between chunks can change as the arena is
+    /// reset or cloned.
+    pub fn offset_between<A, B>(&self, from: *const A, to: *const B) -> Option<isize> {
+        let (from, to) = (from as usize, to as usize);
+        self.chunks().find(|chunk| {
+            let start = chunk.as_ptr() as usize;
+            from >= start && from < start + chunk.len()
+        }).and_then(|chunk| {
+            let start = chunk.as_ptr() as usize;
+            if to >= start && to < start + chunk.len() {
+                Some(to.wrapping_sub(from) as isize)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Copy the filled bytes of every chunk, in allocation order, into one
+    /// buffer, e.g. to pass arena-backed data to another process and load
+    /// it there with `Arena::from_flat_bytes()`.
+    ///
+    /// Each byte's position in the buffer is its logical offset, so
+    /// `RelOffset`s carry over. Values in a later chunk only stay aligned
+    /// if the chunks before it were filled to a multiple of their
+    /// alignment, as they always are for a single-chunk arena.
+    pub fn to_flat_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.bytes_used());
+        for chunk in self.chunks() {
+            bytes.extend_from_slice(chunk);
+        }
+        bytes
+    }
+
+    /// Iterate over the filled bytes of each chunk, in allocation order.
+    ///
+    /// Padding inserted between allocations for alignment reads as whatever
+    /// the storage was filled with (zero for `Vec<u8>`, or `0xCD` with the
+    /// `debug-poison` feature), but padding inside an allocated value (e.g.
+    /// between a struct's fields) is whatever the value's copy left there.
+    pub fn chunks(&self) -> Chunks<'_> {
+        let mut chunks = Vec::new();
+        let mut iter: &Chunk<S> = &self.head;
+        loop {
+            chunks.push(iter.data.filled());
+            match iter.next {
+                None => { break; }
+                Some(ref next) => { iter = next; }
+            }
+        }
+        Chunks { chunks: chunks.into_iter().rev() }
+    }
+
+    /// Read back the filled bytes of every chunk, in allocation order, as
+    /// one continuous stream.
+    pub fn reader(&self) -> ArenaReader<'_> {
+        ArenaReader { chunks: self.chunks(), current: &[] }
+    }
+
+    /// Iterate over the filled bytes of each chunk mutably, in allocation
+    /// order, e.g. to transform the arena's contents in place.
+    pub fn chunks_mut(&mut self) -> ChunksMut<'_> {
+        let mut chunks = Vec::new();
+        let mut iter: Option<&mut Chunk<S>> = Some(&mut self.head);
+        while let Some(chunk) = iter {
+            let Chunk { ref mut data, ref mut next, .. } = *chunk;
+            chunks.push(data.filled_mut());
+            iter = next.as_deref_mut();
+        }
+        ChunksMut { chunks: chunks.into_iter().rev() }
+    }
+
+    /// Iterate over the arena's contents as `T`s, in allocation order,
+    /// without going through a `TypedArena`.
+    ///
+    /// Any padding at the start of a chunk, needed to align its first `T`,
+    /// is skipped.
+    ///
+    /// # Safety
+    ///
+    /// Everything allocated in the arena must have been a `T`, allocated
+    /// on its own or in slices, so that each chunk's filled bytes are
+    /// nothing but back-to-back initialized `T`s. Any other allocation,
+    /// even of a type with the same size, or anything that leaves padding
+    /// between allocations (such as a granularity larger than `T`'s size),
+    /// gets reinterpreted as `T`s.
+    pub unsafe fn iter_as<'a, T: Copy + 'a>(&'a self) -> impl Iterator<Item = &'a T> + 'a {
+        let size = mem::size_of::<T>();
+        assert!(size > 0, "Arena can't iterate over zero-sized values");
+        self.chunks().flat_map(move |bytes| {
+            let skip = cmp::min(padding_for(bytes.as_ptr() as usize, mem::align_of::<T>()), bytes.len());
+            let len = (bytes.len() - skip) / size;
+            slice::from_raw_parts(bytes.as_ptr().add(skip) as *const T, len)
+        })
+    }
+
+    /// Feed the contents of the arena into `hasher`, chunk by chunk in
+    /// allocation order. Arenas that went through the same sequence of
+    /// allocations hash identically, subject to the same caveat about
+    /// padding inside values as `chunks()`.
+    pub fn content_hash<H: Hasher>(&self, hasher: &mut H) {
+        for chunk in self.chunks() {
+            hasher.write(chunk);
+        }
+    }
+
+    /// Check the arena's internal invariants, describing the first one found
+    /// broken. Useful in tests after a complicated sequence of operations.
+    ///
+    /// This checks that every chunk's fill length is within its capacity,
+    /// that the chunks' logical offsets follow on from each other, that the
+    /// cached capacity matches the chunks, and that every registered
+    /// destructor points into allocated memory.
+    pub fn validate(&self) -> Result<(), String> {
+        // The chunk list is made of owned boxes, so it always terminates.
+        let chunks: Vec<&Chunk<S>> = {
+            let mut chunks = Vec::new();
+            let mut iter: &Chunk<S> = &self.head;
+            loop {
+                chunks.push(iter);
+                match iter.next {
+                    None => { break; }
+                    Some(ref next) => { iter = next; }
+                }
+            }
+            chunks.reverse();
+            chunks
+        };
+
+        let mut expected_offset = 0;
+        let mut total_capacity = 0;
+        for (index, chunk) in chunks.iter().enumerate() {
+            if chunk.data.len() > chunk.data.capacity() {
+                return Err(format!("chunk {} holds {} bytes but has a capacity of {}",
+                                   index, chunk.data.len(), chunk.data.capacity()));
+            }
+            if chunk.base_offset != expected_offset {
+                return Err(format!("chunk {} starts at offset {} but the chunks before it hold {} bytes",
+                                   index, chunk.base_offset, expected_offset));
+            }
+            expected_offset += chunk.data.len();
+            total_capacity += chunk.data.capacity();
+        }
+        if total_capacity != self.total_capacity {
+            return Err(format!("cached capacity is {} but the chunks hold {}",
+                               self.total_capacity, total_capacity));
+        }
+
+        for entry in self.drops.iter().chain(self.arena_drops.iter().map(|e| &e.entry)) {
+            let address = entry.ptr as usize;
+            let inside = chunks.iter().any(|chunk| {
+                let start = chunk.data.as_ptr() as usize;
+                address >= start && address <= start + chunk.data.len()
+            });
+            if !inside {
+                return Err(format!("destructor registered for {:#x}, outside every chunk", address));
+            }
+        }
+        Ok(())
+    }
+
+    /// Compute the capacity by walking the chunk list, to check the cached
+    /// total against.
+    #[cfg(test)]
+    fn walked_capacity(&self) -> usize {
+        let mut iter: &Chunk<S> = &self.head;
+        let mut total_capacity = 0;
+        loop {
+            total_capacity += iter.data.capacity();
+            match iter.next {
+                None => { return total_capacity; }
+                Some(ref next) => { iter = next; }
+            }
+        }
+    }
+}
+
+/// The position of an allocation, measured in bytes handed out by the
+/// arena before it rather than as an address.
+///
+/// The same sequence of allocations produces the same offsets in any
+/// arena, so these are reproducible across runs where addresses are not.
+/// This holds as long as no allocation needs more alignment than the
+/// system allocator provides for the arena's own buffers (typically 8 or
+/// 16 bytes).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RelOffset(pub u64);
+
+/// A value whose links to other values in the arena are `RelOffset`s, so
+/// that `Arena::serialize_graph()` can follow and rewrite them.
+pub trait Traverse: Copy {
+    /// Call `f` on every link this value holds to another `Self`.
+    fn visit_links<F: FnMut(&mut RelOffset)>(&mut self, f: F);
+}
+
+/// Iterator over the filled bytes of an arena's chunks, returned by
+/// `Arena::chunks()`.
+pub struct Chunks<'a> {
+    chunks: iter::Rev<vec::IntoIter<&'a [u8]>>,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        self.chunks.next()
+    }
+}
+
+/// Reader over the filled bytes of an arena's chunks, returned by
+/// `Arena::reader()`.
+pub struct ArenaReader<'a> {
+    chunks: Chunks<'a>,
+    // What is left of the chunk being read.
+    current: &'a [u8],
+}
+
+impl<'a> io::Read for ArenaReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.chunks.next() {
+                Some(chunk) => { self.current = chunk; }
+                None => { return Ok(0); }
+            }
+        }
+        let count = cmp::min(buf.len(), self.current.len());
+        buf[..count].copy_from_slice(&self.current[..count]);
+        self.current = &self.current[count..];
+        Ok(count)
+    }
+}
+
+/// Iterator over the filled bytes of an arena's chunks, returned by
+/// `Arena::chunks_mut()`.
+pub struct ChunksMut<'a> {
+    chunks: iter::Rev<vec::IntoIter<&'a mut [u8]>>,
+}
+
+impl<'a> Iterator for ChunksMut<'a> {
+    type Item = &'a mut [u8];
+
+    fn next(&mut self) -> Option<&'a mut [u8]> {
+        self.chunks.next()
+    }
+}
+
+/// Iterator over copies of the elements of an arena-allocated slice,
+/// returned by `Allocator::alloc_into_iter()`.
+pub struct ArenaSliceIter<'a, T: 'a> {
+    elems: slice::Iter<'a, T>,
+}
+
+impl<'a, T: Copy> Iterator for ArenaSliceIter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.elems.next().cloned()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.elems.size_hint()
+    }
+}
+
+impl<'a, T: Copy> DoubleEndedIterator for ArenaSliceIter<'a, T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.elems.next_back().cloned()
+    }
+}
+
+impl<'a, T: Copy> ExactSizeIterator for ArenaSliceIter<'a, T> {}
+
+impl<S: ChunkStorage> Drop for Arena<S> {
+    fn drop(&mut self) {
+        self.run_drops();
+        self.bump_generation();
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Arena {
+        Arena::new()
+    }
+}
+
+impl<S: ChunkStorage> fmt::Debug for Arena<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("Arena {{ capacity_bytes: {} }}", self.capacity()))
+    }
+}
+
+/// Allows allocation out of arena.
+#[derive(Debug)]
+pub struct Allocator<'a, S: ChunkStorage + 'a = Vec<u8>> {
+    arena: &'a mut Arena<S>,
+}
+
+/// Treat an array of slots, e.g. from `Allocator::alloc_uninit_array`, as
+/// the initialized array they hold.
+///
+/// # Safety
+///
+/// Every slot must have been initialized.
+pub unsafe fn assume_init<T, const N: usize>(array: &mut [MaybeUninit<T>; N]) -> &mut [T; N] {
+    &mut *(array as *mut [MaybeUninit<T>; N] as *mut [T; N])
+}
+
+/// Report a slice whose size in bytes doesn't fit in a `usize`: by
+/// panicking, or with the `abort-on-overflow` feature by aborting, e.g. so
+/// the panic never unwinds across an FFI boundary.
+#[cold]
+fn slice_overflow(len: usize, element_size: usize) -> ! {
+    if cfg!(feature = "abort-on-overflow") {
+        std::process::abort();
+    }
+    panic!("Arena slice allocation overflow: len={} element_size={}", len, element_size);
+}
+
+/// The number of bytes needed to bring `addr` up to a multiple of `align`,
+/// which must be a power of two.
+#[inline]
+fn padding_for(addr: usize, align: usize) -> usize {
+    addr.wrapping_neg() & (align - 1)
+}
+
+impl<'a, S: ChunkStorage> Allocator<'a, S> {
+    fn try_alloc_raw(&mut self, size: usize, align: usize) -> Result<*mut u8, AllocError> {
+        if size > self.arena.config.max_single_allocation {
+            return Err(AllocError::AllocationTooLarge);
+        }
+        let granularity = self.arena.config.granularity;
+        let size = size.saturating_add(padding_for(size, granularity));
+        let align = cmp::max(align, granularity);
+        if let Some(x) = self.place(size, align) {
+            return Ok(x);
+        }
+        self.arena.grow(size, align)?;
+        Ok(self.place(size, align).expect("Arena's new chunk is too small for the allocation"))
+    }
+
+    /// Allocate in the current chunk if it has room, keeping the arena's
+    /// statistics.
+    fn place(&mut self, size: usize, align: usize) -> Option<*mut u8> {
+        self.place_at(0, size, align)
+    }
+
+    /// Allocate in the chunk `depth` chunks behind the head if it has room,
+    /// keeping the arena's statistics.
+    fn place_at(&mut self, depth: usize, size: usize, align: usize) -> Option<*mut u8> {
+        let (x, padding, offset) = {
+            let mut chunk: &mut Chunk<S> = &mut self.arena.head;
+            for _ in 0..depth {
+                chunk = chunk.next.as_deref_mut()?;
+            }
+            let start = chunk.data.as_ptr() as usize;
+            let tip = start + chunk.data.len();
+            let x = chunk.attempt_alloc(size, align)?;
+            (x, x as usize - tip, x as usize - start)
+        };
+        self.arena.padding_bytes += padding;
+        self.arena.allocation_count += 1;
+        self.arena.max_align = cmp::max(self.arena.max_align, align);
+        #[cfg(feature = "debug-track")]
+        self.arena.boundaries.push((x as usize, size));
+        if let Some(ref mut trace) = self.arena.trace {
+            (trace.0)(size, align, offset);
+        }
+        Some(x)
+    }
+
+    /// Allocate a copy of an object in a particular chunk, numbered oldest
+    /// first as `Arena::chunks()` yields them, e.g. to keep hot data
+    /// together in one chunk. Returns `None`, without growing the arena, if
+    /// there is no such chunk, it is too full, or `T` is over the arena's
+    /// `max_single_allocation`. While a mark from `push_mark()` or a
+    /// template from `Arena::freeze_template()` is active, only the current
+    /// chunk can be chosen, since rewinding only truncates that one.
+    ///
+    /// Allocating in an older chunk changes the logical offsets of
+    /// everything in the chunks after it, so `RelOffset`s into those don't
+    /// carry over.
+    pub fn alloc_in_chunk<T: Copy>(&mut self, chunk_index: usize, elem: T) -> Option<&'a mut T> {
+        let chunk_count = self.arena.chunk_count();
+        if chunk_index >= chunk_count {
+            return None;
+        }
+        let depth = chunk_count - 1 - chunk_index;
+        if depth > 0 && (!self.arena.marks.is_empty() || self.arena.template.is_some()) {
+            return None;
+        }
+        let granularity = self.arena.config.granularity;
+        let size = mem::size_of::<T>();
+        if size > self.arena.config.max_single_allocation {
+            return None;
+        }
+        let size = size + padding_for(size, granularity);
+        let align = cmp::max(mem::align_of::<T>(), granularity);
+        let memory = self.place_at(depth, size, align)? as *mut T;
+        if depth > 0 {
+            self.arena.renumber();
+        }
+        unsafe {
+            ptr::write(memory, elem);
+            Some(&mut *memory)
+        }
+    }
+
+    fn alloc_bytes(&mut self, size: usize, align: usize) -> *mut u8 {
+        self.try_alloc_raw(size, align).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Allocate `size` bytes aligned to `align`, leaving them
+    /// uninitialized. This is the primitive all the other allocation
+    /// methods are built on, for building typed layers of your own.
+    ///
+    /// # Safety
+    ///
+    /// `align` must be a power of two. The memory must be initialized
+    /// before it is read, and must not be used after the arena is reset or
+    /// dropped.
+    pub unsafe fn alloc_raw(&mut self, size: usize, align: usize) -> *mut u8 {
+        self.check_alignment(align).unwrap_or_else(|e| panic!("{}", e));
+        self.alloc_bytes(size, align)
+    }
+
+    /// Reject an explicitly requested alignment above the arena's
+    /// `max_alignment`.
+    fn check_alignment(&self, align: usize) -> Result<(), AllocError> {
+        if align > self.arena.config.max_alignment {
+            return Err(AllocError::AlignmentTooLarge);
+        }
+        Ok(())
+    }
+
+    /// Allocate a copy of an object aligned to at least `align`, e.g. to
+    /// start it on a cache line.
+    ///
+    /// `align` must be a power of two. An `align` smaller than `T`'s own
+    /// alignment is raised to it. Panics if `align` is above the arena's
+    /// `max_alignment`.
+    pub fn alloc_aligned<T: Copy>(&mut self, elem: T, align: usize) -> &'a mut T {
+        self.try_alloc_aligned(elem, align).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `alloc_aligned`, but fails instead of panicking if `align` is
+    /// too large or the allocation exceeds the arena's budget.
+    pub fn try_alloc_aligned<T: Copy>(&mut self, elem: T, align: usize)
+        -> Result<&'a mut T, AllocError>
+    {
+        assert!(align.is_power_of_two(), "Arena alignment must be a power of two");
+        self.check_alignment(align)?;
+        let align = cmp::max(align, mem::align_of::<T>());
+        let memory = self.try_alloc_raw(mem::size_of::<T>(), align)? as *mut T;
+        unsafe {
+            ptr::write(memory, elem);
+            Ok(&mut *memory)
+        }
+    }
+
+    /// Allocate a copy of an object
+    #[cfg_attr(feature = "debug-track", track_caller)]
+    pub fn alloc<T: Copy>(&mut self, elem: T) -> &'a mut T {
+        #[cfg(feature = "debug-track")]
+        self.arena.report.push((*Location::caller(), mem::size_of::<T>()));
+        let memory = self.alloc_bytes(mem::size_of::<T>(), mem::align_of::<T>()) as *mut T;
+        unsafe {
+            ptr::write(memory, elem);
+            &mut *memory
+        }
+    }
+
+    /// Allocate a copy of an object, or fail if the arena cannot grow to
+    /// fit it.
+    pub fn try_alloc<T: Copy>(&mut self, elem: T) -> Result<&'a mut T, AllocError> {
+        let memory = self.try_alloc_raw(mem::size_of::<T>(), mem::align_of::<T>())? as *mut T;
+        unsafe {
+            ptr::write(memory, elem);
+            Ok(&mut *memory)
+        }
+    }
+
+    /// Allocate a copy of an object in a block whose size and alignment are
+    /// both the next power of two at or above its size, so allocations
+    /// tile like a buddy allocator's.
+    pub fn alloc_pow2<T: Copy>(&mut self, elem: T) -> &'a mut T {
+        let block = cmp::max(mem::size_of::<T>(), 1).next_power_of_two();
+        let align = cmp::max(block, mem::align_of::<T>());
+        let memory = self.alloc_bytes(block, align) as *mut T;
+        unsafe {
+            ptr::write(memory, elem);
+            &mut *memory
+        }
+    }
+
+    /// Allocate a copy of an object, returning a reference that only lives
+    /// as long as this borrow of the allocator.
+    ///
+    /// Because the reference needn't outlive the arena's borrow, the value
+    /// may borrow things that don't either. This works:
+    ///
+    /// ```
+    /// use copy_arena::Arena;
+    ///
+    /// let mut arena = Arena::new();
+    /// let mut allocator = arena.allocator();
+    /// {
+    ///     let local = 5u32;
+    ///     let scratch = allocator.alloc_scoped(&local);
+    ///     assert_eq!(**scratch, 5);
+    /// }
+    /// allocator.alloc(1u32);
+    /// ```
+    ///
+    /// while the same thing with `alloc` does not compile:
+    ///
+    /// ```compile_fail
+    /// use copy_arena::Arena;
+    ///
+    /// let mut arena = Arena::new();
+    /// let mut allocator = arena.allocator();
+    /// {
+    ///     let local = 5u32;
+    ///     let scratch = allocator.alloc(&local);
+    ///     assert_eq!(**scratch, 5);
+    /// }
+    /// allocator.alloc(1u32);
+    /// ```
+    pub fn alloc_scoped<T: Copy>(&mut self, elem: T) -> &mut T {
+        let memory = self.alloc_bytes(mem::size_of::<T>(), mem::align_of::<T>()) as *mut T;
+        unsafe {
+            ptr::write(memory, elem);
+            &mut *memory
+        }
+    }
+
+    /// Allocate two objects back to back in one allocation, with `b`
+    /// starting right after `a` (after any padding `B`'s alignment needs).
+    pub fn alloc_pair<A: Copy, B: Copy>(&mut self, a: A, b: B) -> (&'a mut A, &'a mut B) {
+        let b_offset = mem::size_of::<A>() + padding_for(mem::size_of::<A>(), mem::align_of::<B>());
+        let size = b_offset + mem::size_of::<B>();
+        let align = cmp::max(mem::align_of::<A>(), mem::align_of::<B>());
+        let memory = self.alloc_bytes(size, align);
+        unsafe {
+            let a_ptr = memory as *mut A;
+            let b_ptr = memory.add(b_offset) as *mut B;
+            ptr::write(a_ptr, a);
+            ptr::write(b_ptr, b);
+            (&mut *a_ptr, &mut *b_ptr)
+        }
+    }
+
+    /// Allocate three objects back to back in one allocation, like
+    /// `alloc_pair`.
+    pub fn alloc_triple<A: Copy, B: Copy, C: Copy>(&mut self, a: A, b: B, c: C)
+        -> (&'a mut A, &'a mut B, &'a mut C)
+    {
+        let b_offset = mem::size_of::<A>() + padding_for(mem::size_of::<A>(), mem::align_of::<B>());
+        let b_end = b_offset + mem::size_of::<B>();
+        let c_offset = b_end + padding_for(b_end, mem::align_of::<C>());
+        let size = c_offset + mem::size_of::<C>();
+        let align = cmp::max(mem::align_of::<A>(), cmp::max(mem::align_of::<B>(), mem::align_of::<C>()));
+        let memory = self.alloc_bytes(size, align);
+        unsafe {
+            let a_ptr = memory as *mut A;
+            let b_ptr = memory.add(b_offset) as *mut B;
+            let c_ptr = memory.add(c_offset) as *mut C;
+            ptr::write(a_ptr, a);
+            ptr::write(b_ptr, b);
+            ptr::write(c_ptr, c);
+            (&mut *a_ptr, &mut *b_ptr, &mut *c_ptr)
+        }
+    }
+
+    /// Allocate a copy of an object at the start of a fresh chunk sized
+    /// just for it (plus slack for its alignment), e.g. to keep hot data
+    /// away from neighbors that churn. Whatever was left in the previous
+    /// chunk is wasted.
+    ///
+    /// The value starts at the chunk's base, or as close after it as its
+    /// alignment allows.
+    pub fn alloc_isolated<T: Copy>(&mut self, elem: T) -> &'a mut T {
+        let granularity = self.arena.config.granularity;
+        let size = mem::size_of::<T>();
+        if size > self.arena.config.max_single_allocation {
+            panic!("{}", AllocError::AllocationTooLarge);
+        }
+        let size = size + padding_for(size, granularity);
+        let align = cmp::max(mem::align_of::<T>(), granularity);
+        self.arena.grow_by(size, align, false).unwrap_or_else(|e| panic!("{}", e));
+        let memory = self.place(size, align).expect("Arena's new chunk is too small for the allocation") as *mut T;
+        unsafe {
+            ptr::write(memory, elem);
+            &mut *memory
+        }
+    }
+
+    /// Allocate a copy of an object behind a guard that, in debug builds,
+    /// panics if it is dereferenced after the arena is reset or dropped.
+    pub fn alloc_guarded<T: Copy>(&mut self, elem: T) -> Guarded<'a, T> {
+        let current = self.arena.generation.get_or_insert_with(Default::default).clone();
+        Guarded::new(self.alloc(elem), current)
+    }
+
+    /// Allocate a copy of an object, then, in debug builds, panic unless
+    /// `check` accepts the allocated value, so broken invariants show up
+    /// where the value is made. Release builds skip the check.
+    pub fn alloc_checked<T: Copy, F: FnOnce(&T) -> bool>(&mut self, elem: T, check: F) -> &'a mut T {
+        let value = self.alloc(elem);
+        debug_assert!(check(value), "Arena allocation failed its check");
+        value
+    }
+
+    /// Allocate a copy of an object, returning a pointer to it instead of a
+    /// reference, for data structures that manage lifetimes themselves.
+    ///
+    /// The pointer is valid until the arena is reset or dropped.
+    pub fn alloc_nonnull<T: Copy>(&mut self, elem: T) -> NonNull<T> {
+        let memory = self.alloc_bytes(mem::size_of::<T>(), mem::align_of::<T>()) as *mut T;
+        unsafe {
+            ptr::write(memory, elem);
+            NonNull::new_unchecked(memory)
+        }
+    }
+
+    /// Allocate a copy of an object, copying straight from `elem`.
+    ///
+    /// Unlike `alloc`, the value is never moved onto the stack first, which
+    /// saves a copy for large values like `[u8; 4096]`. Copying 4 KB arrays
+    /// out of a `black_box` in a release build on x86-64 (rustc 1.95), this
+    /// took about 290 ns per allocation against 330 ns for `alloc`. When
+    /// the optimizer can elide `alloc`'s move, the two cost the same.
+    pub fn alloc_copy<T: Copy>(&mut self, elem: &T) -> &'a mut T {
+        let memory = self.alloc_bytes(mem::size_of::<T>(), mem::align_of::<T>()) as *mut T;
+        unsafe {
+            ptr::copy_nonoverlapping(elem, memory, 1);
+            &mut *memory
+        }
+    }
+
+    /// Allocate a copy of an object, returning its logical offset in the
+    /// arena instead of a reference. `Arena::resolve()` turns the offset
+    /// back into a reference.
+    pub fn alloc_offset<T: Copy>(&mut self, elem: T) -> RelOffset {
+        let memory = self.alloc_bytes(mem::size_of::<T>(), mem::align_of::<T>());
+        unsafe { ptr::write(memory as *mut T, elem); }
+        let head = &self.arena.head;
+        RelOffset((head.base_offset + (memory as usize - head.data.as_ptr() as usize)) as u64)
+    }
+
+    /// Move a value that may need dropping into the arena, registering its
+    /// destructor to run when the arena is reset or dropped.
+    fn alloc_owned<T: Send + 'static>(&mut self, value: T) -> &'a mut T {
+        let memory = self.alloc_bytes(mem::size_of::<T>(), mem::align_of::<T>()) as *mut T;
+        unsafe { ptr::write(memory, value); }
+        if mem::needs_drop::<T>() {
+            self.arena.drops.push(DropEntry { ptr: memory as *mut u8, drop: drop_value::<T> });
+        }
+        unsafe { &mut *memory }
+    }
+
+    /// Move a value that may need dropping into the arena, registering its
+    /// destructor to run when the arena is next reset, or dropped.
+    pub fn alloc_reset_dropped<T: Send + 'static>(&mut self, value: T) -> &'a mut T {
+        self.alloc_owned(value)
+    }
+
+    /// Move a value that may need dropping into the arena, registering its
+    /// destructor to run only when the arena itself is dropped.
+    ///
+    /// Resetting the arena still discards the value as far as `'a` is
+    /// concerned, but rather than dropping it, moves it to the heap to wait
+    /// for the arena's end.
+    pub fn alloc_arena_dropped<T: Send + 'static>(&mut self, value: T) -> &'a mut T {
+        let memory = self.alloc_bytes(mem::size_of::<T>(), mem::align_of::<T>()) as *mut T;
+        unsafe { ptr::write(memory, value); }
+        if mem::needs_drop::<T>() {
+            self.arena.arena_drops.push(ArenaDropEntry {
+                entry: DropEntry { ptr: memory as *mut u8, drop: drop_value::<T> },
+                keep: keep_value::<T>,
+            });
+        }
+        unsafe { &mut *memory }
+    }
+
+    /// Move a closure into the arena, avoiding a heap allocation for it.
+    ///
+    /// Closures aren't `Copy`, so the arena runs the closure's destructor
+    /// (dropping whatever it captured) when the arena is reset or dropped.
+    /// That may be after `'a` ends, which is why the closure can't borrow
+    /// anything and must be `'static`.
+    pub fn alloc_fn<F: FnMut() + Send + 'static>(&mut self, f: F) -> &'a mut dyn FnMut() {
+        self.alloc_owned(f)
+    }
+
+    /// Allocate an object built from its index, returning both.
+    ///
+    /// Indices count up from zero across all `alloc_indexed` calls on the
+    /// same arena, so every object allocated this way can know its own
+    /// stable index.
+    pub fn alloc_indexed<T: Copy, F: FnOnce(usize) -> T>(&mut self, f: F) -> (&'a mut T, usize) {
+        let index = self.arena.next_index;
+        self.arena.next_index += 1;
+        (self.alloc(f(index)), index)
+    }
+
+    /// Allocate a copy of an object, along with its sequence number.
+    ///
+    /// Sequence numbers count up by one per `alloc_numbered` call, whatever
+    /// the size of the object, so two runs making the same calls number
+    /// them identically. They survive `reset()`; only
+    /// `Arena::reset_sequence()` starts them over.
+    pub fn alloc_numbered<T: Copy>(&mut self, elem: T) -> (u64, &'a mut T) {
+        let number = self.arena.sequence;
+        self.arena.sequence += 1;
+        (number, self.alloc(elem))
+    }
+
+    /// Allocate a copy of an object, along with the arena's id, to check
+    /// with `Arena::owns_id()` wherever the reference ends up.
+    pub fn alloc_tagged<T: Copy>(&mut self, elem: T) -> (u64, &'a mut T) {
+        (self.arena.id, self.alloc(elem))
+    }
+
+    /// Allocate each of `items` as a node of a singly-linked list, returning
+    /// the head.
+    ///
+    /// `link` is called on each node before it is allocated, to point it at
+    /// the node that should follow it. The list keeps the order of `items`;
+    /// they are collected first and built back to front so each node's
+    /// successor already exists.
+    pub fn alloc_linked<T: Copy, I, L>(&mut self, items: I, link: L) -> Option<&'a T>
+        where I: IntoIterator<Item = T>,
+              L: Fn(&mut T, Option<&'a T>)
+    {
+        let items: Vec<T> = items.into_iter().collect();
+        let mut head: Option<&'a T> = None;
+        for mut item in items.into_iter().rev() {
+            link(&mut item, head);
+            head = Some(self.alloc(item));
+        }
+        head
+    }
+
+    /// Allocate a default-valued object
+    pub fn alloc_default<T: Copy+Default>(&mut self) -> &'a mut T {
+        self.alloc(Default::default())
+    }
+
+    /// Allocate a default-valued object to be filled in place through the
+    /// returned `ArenaBuild`, a closure at a time.
+    pub fn build<T: Copy+Default>(&mut self) -> ArenaBuild<'a, T> {
+        ArenaBuild::new(self.alloc_default())
+    }
+
+    /// Allocate and leave uninitialized a slice of the given length
+    fn alloc_slice_raw<T>(&mut self, len: usize) -> &'a mut [T] {
+        self.alloc_slice_raw_aligned(len, mem::align_of::<T>())
+    }
+
+    /// Allocate and leave uninitialized a slice of the given length whose
+    /// base is aligned to `align`, which must be at least `T`'s alignment.
+    fn alloc_slice_raw_aligned<T>(&mut self, len: usize, align: usize) -> &'a mut [T] {
+        self.try_alloc_slice_raw_aligned(len, align).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_alloc_slice_raw_aligned<T>(&mut self, len: usize, align: usize)
+        -> Result<&'a mut [T], AllocError>
+    {
+        if len > self.arena.config.max_slice_len {
+            return Err(AllocError::SliceTooLong);
+        }
+        let element_size = mem::size_of::<[T;2]>() / 2;
+        assert_eq!(mem::size_of::<[T;7]>(), 7 * element_size);
+        let byte_count = element_size.checked_mul(len)
+            .unwrap_or_else(|| slice_overflow(len, element_size));
+        let memory = self.try_alloc_raw(byte_count, align)? as *mut T;
+        // The stride is a multiple of T's alignment, so an aligned base
+        // lines up every element.
+        debug_assert!(align >= mem::align_of::<T>() && padding_for(element_size, mem::align_of::<T>()) == 0);
+        debug_assert_eq!(padding_for(memory as usize, align), 0);
+        Ok(unsafe { slice::from_raw_parts_mut(memory, len) })
+    }
+
+    /// Allocate a slice of the given length without initializing it.
+    pub fn alloc_slice_uninit<T: Copy>(&mut self, len: usize) -> &'a mut [MaybeUninit<T>] {
+        self.alloc_slice_raw(len)
+    }
+
+    /// Allocate a slice of `len` elements to be filled in order through the
+    /// returned cursor, which can be kept and filled later without holding
+    /// on to this allocator.
+    pub fn alloc_fill_cursor<T: Copy>(&mut self, len: usize) -> FillCursor<'a, T> {
+        FillCursor::new(self.alloc_slice_uninit(len))
+    }
+
+    /// Reserve space for a `T` to be written later through the returned
+    /// `Patch`, e.g. a length prefix that is only known once the data
+    /// after it has been allocated.
+    pub fn reserve_patch<T: Copy>(&mut self) -> Patch<'a, T> {
+        let memory = self.alloc_bytes(mem::size_of::<T>(), mem::align_of::<T>());
+        Patch::new(unsafe { &mut *(memory as *mut MaybeUninit<T>) })
+    }
+
+    /// Allocate a tree node holding `value`, along with a copy of the
+    /// references to its `children`, so trees can be built bottom-up
+    /// without juggling the children slices' lifetimes.
+    pub fn alloc_node<T: Copy>(&mut self, value: T, children: &[&'a TreeNode<'a, T>])
+        -> &'a mut TreeNode<'a, T>
+    {
+        let children = self.alloc_slice(children);
+        self.alloc(TreeNode { value, children })
+    }
+
+    /// Copy an array, taken by value, into an arena slice of length `N`,
+    /// e.g. straight from an array literal.
+    pub fn alloc_from_array<T: Copy, const N: usize>(&mut self, arr: [T; N]) -> &'a mut [T] {
+        self.alloc_slice(&arr)
+    }
+
+    /// Allocate an array of `N` uninitialized slots, to fill in before
+    /// reading them back through `assume_init`.
+    pub fn alloc_uninit_array<T: Copy, const N: usize>(&mut self) -> &'a mut [MaybeUninit<T>; N] {
+        let memory = self.alloc_bytes(mem::size_of::<[T; N]>(), mem::align_of::<T>());
+        unsafe { &mut *(memory as *mut [MaybeUninit<T>; N]) }
+    }
+
+    /// Allocate a copy of a slice
+    #[cfg_attr(feature = "debug-track", track_caller)]
+    pub fn alloc_slice<T: Copy>(&mut self, elems: &[T]) -> &'a mut [T] {
+        #[cfg(feature = "debug-track")]
+        self.arena.report.push((*Location::caller(), mem::size_of_val(elems)));
+        let slice = self.alloc_slice_raw(elems.len());
+        for (dest, src) in slice.iter_mut().zip(elems.iter()) {
+            *dest = *src;
+        }
+        slice
+    }
+
+    /// Allocate a copy of `init` followed by `spare` default-valued
+    /// elements, split at `at` into a shared prefix and a mutable suffix,
+    /// e.g. to keep writing after data that should no longer change.
+    ///
+    /// The halves are contiguous in the arena. Panics if `at` is past the
+    /// end of the allocation.
+    pub fn alloc_slice_split_mut<T: Copy+Default>(&mut self, init: &[T], spare: usize, at: usize)
+        -> (&'a [T], &'a mut [T])
+    {
+        let len = init.len().checked_add(spare).expect("Arena slice size overflow");
+        assert!(at <= len, "Arena split point is past the end of the slice");
+        let slice = self.alloc_slice_raw(len);
+        let (filled, rest) = slice.split_at_mut(init.len());
+        filled.copy_from_slice(init);
+        for dest in rest.iter_mut() {
+            *dest = T::default();
+        }
+        let (prefix, suffix) = slice.split_at_mut(at);
+        (prefix, suffix)
+    }
+
+    /// Copy `src` into the arena if `must_own` is set, and otherwise just
+    /// wrap the borrow, so either way the caller gets the same type.
+    pub fn to_arena_cow<T: Copy>(&mut self, src: &'a [T], must_own: bool) -> ArenaCow<'a, T> {
+        if must_own {
+            ArenaCow::Owned(self.alloc_slice(src))
+        } else {
+            ArenaCow::Borrowed(src)
+        }
+    }
+
+    /// Allocate a copy of a slice, returning an iterator over copies of its
+    /// elements, for when only the values are needed.
+    pub fn alloc_into_iter<T: Copy>(&mut self, elems: &[T]) -> ArenaSliceIter<'a, T> {
+        let slice: &'a [T] = self.alloc_slice(elems);
+        ArenaSliceIter { elems: slice.iter() }
+    }
+
+    /// Allocate a copy of a string.
+    #[cfg_attr(feature = "debug-track", track_caller)]
+    pub fn alloc_str(&mut self, s: &str) -> &'a mut str {
+        let bytes = self.alloc_slice(s.as_bytes());
+        unsafe { str::from_utf8_unchecked_mut(bytes) }
+    }
+
+    /// Allocate a copy of the `len` elements at `src`, e.g. an array handed
+    /// over from C.
+    ///
+    /// # Safety
+    ///
+    /// `src` must point to `len` initialized, properly aligned `T`s.
+    pub unsafe fn alloc_slice_from_raw<T: Copy>(&mut self, src: *const T, len: usize) -> &'a mut [T] {
+        let slice = self.alloc_slice_raw(len);
+        ptr::copy_nonoverlapping(src, slice.as_mut_ptr(), len);
+        slice
+    }
+
+    /// Allocate a copy of a slice as the next piece of a logical stream,
+    /// returning the stream offset it starts at along with the copy.
+    ///
+    /// The offset is the number of bytes emitted by all earlier `emit`
+    /// calls on the same arena, regardless of padding or chunk boundaries,
+    /// e.g. to patch jump targets in emitted bytecode.
+    pub fn emit<T: Copy>(&mut self, elems: &[T]) -> (usize, &'a mut [T]) {
+        let offset = self.arena.emitted;
+        let slice = self.alloc_slice(elems);
+        self.arena.emitted += mem::size_of_val(slice);
+        (offset, slice)
+    }
+
+    /// Allocate a copy of a slice, wrapped in your own type `W`, e.g. a
+    /// newtype that dereferences to the slice and adds methods of its own.
+    #[cfg_attr(feature = "debug-track", track_caller)]
+    pub fn alloc_slice_as<T: Copy + 'a, W: From<&'a mut [T]>>(&mut self, elems: &[T]) -> W {
+        W::from(self.alloc_slice(elems))
+    }
+
+    /// Allocate a copy of a slice, or fail if the arena cannot grow to fit
+    /// it.
+    pub fn try_alloc_slice<T: Copy>(&mut self, elems: &[T]) -> Result<&'a mut [T], AllocError> {
+        let slice = self.try_alloc_slice_raw_aligned(elems.len(), mem::align_of::<T>())?;
+        slice.copy_from_slice(elems);
+        Ok(slice)
+    }
+
+    /// Allocate one contiguous slice holding every fragment from `iter`,
+    /// one after another.
+    ///
+    /// The fragments are gathered (as references, not copies) before
+    /// anything is allocated, so the result is a single allocation of
+    /// exactly the total length.
+    pub fn alloc_slice_flatten<'b, T: Copy + 'b, I>(&mut self, iter: I) -> &'a mut [T]
+        where I: Iterator<Item = &'b [T]>
+    {
+        let fragments: Vec<&'b [T]> = iter.collect();
+        let len = fragments.iter()
+            .try_fold(0usize, |total, fragment| total.checked_add(fragment.len()))
+            .expect("Arena slice size overflow");
+
+        let slice = self.alloc_slice_raw(len);
+        let mut rest = &mut slice[..];
+        for fragment in fragments {
+            let (dest, remainder) = rest.split_at_mut(fragment.len());
+            dest.copy_from_slice(fragment);
+            rest = remainder;
+        }
+        slice
+    }
+
+    /// Allocate `a` followed by `b` as one contiguous slice, e.g. to
+    /// straighten out the two halves of a wrapped ring buffer. Also returns
+    /// the index where `b` begins.
+    pub fn alloc_slice_join2<T: Copy>(&mut self, a: &[T], b: &[T]) -> (&'a mut [T], usize) {
+        let len = a.len().checked_add(b.len()).expect("Arena slice size overflow");
+        let slice = self.alloc_slice_raw(len);
+        slice[..a.len()].copy_from_slice(a);
+        slice[a.len()..].copy_from_slice(b);
+        (slice, a.len())
+    }
+
+    /// Allocate a slice of `total_len` elements filled by repeating
+    /// `pattern` over and over, e.g. for a test fill pattern. The last
+    /// repetition is cut short if `pattern` doesn't divide `total_len`.
+    ///
+    /// Panics if `pattern` is empty but `total_len` isn't zero.
+    pub fn alloc_slice_tiled<T: Copy>(&mut self, pattern: &[T], total_len: usize) -> &'a mut [T] {
+        assert!(!pattern.is_empty() || total_len == 0, "Arena can't tile an empty pattern");
+        let slice = self.alloc_slice_raw(total_len);
+        for tile in slice.chunks_mut(cmp::max(pattern.len(), 1)) {
+            tile.copy_from_slice(&pattern[..tile.len()]);
+        }
+        slice
+    }
+
+    /// Allocate a copy of a slice followed by `sentinel`, e.g. for C code
+    /// that expects an array ending in a terminator. The returned slice
+    /// includes the sentinel as its last element.
+    pub fn alloc_slice_terminated<T: Copy>(&mut self, elems: &[T], sentinel: T) -> &'a mut [T] {
+        self.alloc_slice_join2(elems, &[sentinel]).0
+    }
+
+    /// Allocate each element of a slice paired with its index, like
+    /// collecting `elems.iter().enumerate()`.
+    pub fn alloc_slice_enumerated<T: Copy>(&mut self, elems: &[T]) -> &'a mut [(usize, T)] {
+        let slice = self.alloc_slice_raw(elems.len());
+        for (dest, pair) in slice.iter_mut().zip(elems.iter().cloned().enumerate()) {
+            *dest = pair;
+        }
+        slice
+    }
+
+    /// Allocate a copy of a slice with its elements in reverse order,
+    /// reversing them during the copy rather than in a second pass.
+    pub fn alloc_slice_reversed<T: Copy>(&mut self, elems: &[T]) -> &'a mut [T] {
+        let slice = self.alloc_slice_raw(elems.len());
+        for (dest, src) in slice.iter_mut().zip(elems.iter().rev()) {
+            *dest = *src;
+        }
+        slice
+    }
+
+    /// Allocate a copy of a slice for SIMD processing: its base is aligned
+    /// to `vector_bytes`, and the allocation is padded out to a whole
+    /// number of vectors, so full-width loads past the last element stay
+    /// inside the arena's memory. The returned slice covers just `elems`.
+    ///
+    /// `vector_bytes` must be a power of two. Panics if it is above the
+    /// arena's `max_alignment`.
+    pub fn alloc_slice_simd<T: Copy>(&mut self, elems: &[T], vector_bytes: usize) -> &'a mut [T] {
+        assert!(vector_bytes.is_power_of_two(), "Arena vector size must be a power of two");
+        self.check_alignment(vector_bytes).unwrap_or_else(|e| panic!("{}", e));
+        if elems.len() > self.arena.config.max_slice_len {
+            panic!("{}", AllocError::SliceTooLong);
+        }
+        let byte_count = mem::size_of_val(elems);
+        let padded = byte_count.checked_add(padding_for(byte_count, vector_bytes))
+            .expect("Arena slice size overflow");
+        let align = cmp::max(mem::align_of::<T>(), vector_bytes);
+        let memory = self.alloc_bytes(padded, align) as *mut T;
+        unsafe {
+            ptr::copy_nonoverlapping(elems.as_ptr(), memory, elems.len());
+            slice::from_raw_parts_mut(memory, elems.len())
+        }
+    }
+
+    /// Allocate `len` default-valued elements spaced `stride` bytes apart,
+    /// with the first aligned to `stride`, e.g. so that with a stride of 64
+    /// every element sits alone on its own cache line.
+    ///
+    /// `stride` must be a power of two at least as large as `T`. Panics if
+    /// it is above the arena's `max_alignment`.
+    pub fn alloc_slice_padded<T: Copy+Default>(&mut self, len: usize, stride: usize)
+        -> PaddedSlice<'a, T>
+    {
+        assert!(stride.is_power_of_two() && stride >= mem::size_of::<T>(),
+                "Arena stride must be a power of two no smaller than the element");
+        self.check_alignment(stride).unwrap_or_else(|e| panic!("{}", e));
+        if len > self.arena.config.max_slice_len {
+            panic!("{}", AllocError::SliceTooLong);
+        }
+        let byte_count = stride.checked_mul(len).unwrap_or_else(|| slice_overflow(len, stride));
+        let align = cmp::max(stride, mem::align_of::<T>());
+        let base = self.alloc_bytes(byte_count, align);
+        for index in 0..len {
+            unsafe { ptr::write(base.add(index * stride) as *mut T, T::default()); }
+        }
+        unsafe { PaddedSlice::new(base, len, stride) }
+    }
+
+    /// Allocate a copy of a slice with runs of equal consecutive elements
+    /// collapsed to one, like `Vec::dedup`. Only the kept elements take up
+    /// space in the arena.
+    pub fn alloc_slice_dedup<T: Copy+PartialEq>(&mut self, elems: &[T]) -> &'a mut [T] {
+        let starts_run = |idx: usize| idx == 0 || elems[idx - 1] != elems[idx];
+        let len = (0..elems.len()).filter(|&idx| starts_run(idx)).count();
+
+        let slice = self.alloc_slice_raw(len);
+        let kept = (0..elems.len()).filter(|&idx| starts_run(idx));
+        for (dest, src) in slice.iter_mut().zip(kept) {
+            *dest = elems[src];
+        }
+        slice
+    }
+
+    /// Allocate a copy of a slice, sorted. The sort happens in place in the
+    /// arena, so no scratch buffer is needed.
+    pub fn alloc_slice_sorted<T: Copy+Ord>(&mut self, elems: &[T]) -> &'a mut [T] {
+        self.alloc_slice_sorted_by(elems, Ord::cmp)
+    }
+
+    /// Allocate a copy of a slice, sorted with the comparator `compare`.
+    pub fn alloc_slice_sorted_by<T, F>(&mut self, elems: &[T], compare: F) -> &'a mut [T]
+        where T: Copy, F: FnMut(&T, &T) -> cmp::Ordering
+    {
+        let slice = self.alloc_slice(elems);
+        slice.sort_unstable_by(compare);
+        slice
+    }
+
+    /// Allocate a copy of a slice that does not straddle a multiple of
+    /// `boundary` (e.g. a 4 KB page boundary, for DMA).
+    ///
+    /// If the slice would cross one where it would otherwise go, it is
+    /// moved up to start at the next boundary instead. `boundary` must be
+    /// a power of two, and the slice can be at most `boundary` bytes.
+    /// Panics if `boundary` is above the arena's `max_alignment`, since the
+    /// slice may need to be aligned to it.
+    pub fn alloc_slice_no_cross<T: Copy>(&mut self, elems: &[T], boundary: usize) -> &'a mut [T] {
+        assert!(boundary.is_power_of_two(), "Arena boundary must be a power of two");
+        self.check_alignment(boundary).unwrap_or_else(|e| panic!("{}", e));
+        let byte_count = mem::size_of_val(elems);
+        assert!(byte_count <= boundary, "Arena slice is larger than its boundary");
+
+        // Place it where alloc_slice_raw_aligned would, as rounded up to the
+        // granularity.
+        let granularity = self.arena.config.granularity;
+        let align = cmp::max(mem::align_of::<T>(), granularity);
+        let rounded_count = byte_count + padding_for(byte_count, granularity);
+        let fits_in_place = {
+            let data = &self.arena.head.data;
+            let tip = data.as_ptr() as usize + data.len();
+            let start = tip + padding_for(tip, align);
+            let free = data.capacity() - data.len();
+            start - tip + rounded_count <= free
+                && (byte_count == 0 || start / boundary == (start + byte_count - 1) / boundary)
+        };
+
+        // Starting on a boundary always leaves room for the whole slice.
+        let align = if fits_in_place { align } else { cmp::max(align, boundary) };
+        let slice = self.alloc_slice_raw_aligned(elems.len(), align);
+        slice.copy_from_slice(elems);
+        slice
+    }
+
+    /// Extend a slice by `additional` default-valued elements.
+    ///
+    /// If `slice` was the most recent allocation and the current chunk has
+    /// room, it is extended in place. Otherwise it is copied into a new,
+    /// larger allocation, and the old one is wasted.
+    pub fn grow_slice<T: Copy+Default>(&mut self, slice: &'a mut [T], additional: usize)
+        -> &'a mut [T]
+    {
+        let element_size = mem::size_of::<T>();
+        let old_len = slice.len();
+        let new_len = old_len.checked_add(additional).expect("Arena slice size overflow");
+        if new_len > self.arena.config.max_slice_len {
+            panic!("{}", AllocError::SliceTooLong);
+        }
+        let extra_bytes = element_size.checked_mul(additional)
+            .unwrap_or_else(|| slice_overflow(additional, element_size));
+        if mem::size_of_val(slice) + extra_bytes > self.arena.config.max_single_allocation {
+            panic!("{}", AllocError::AllocationTooLarge);
+        }
+
+        // The slice's allocation, like every allocation, was rounded up to
+        // the granularity, and so must its extension be.
+        let granularity = self.arena.config.granularity;
+        let old_bytes = mem::size_of_val(slice);
+        let old_rounded = old_bytes + padding_for(old_bytes, granularity);
+        let new_bytes = old_bytes + extra_bytes;
+        let new_rounded = new_bytes.saturating_add(padding_for(new_bytes, granularity));
+
+        let grown = {
+            let data = &mut self.arena.head.data;
+            let tip = data.as_ptr() as usize + data.len();
+            let end = slice.as_ptr() as usize + old_rounded;
+            let in_head = slice.as_ptr() as usize >= data.as_ptr() as usize;
+            if in_head && end == tip && new_rounded - old_rounded <= data.capacity() - data.len() {
+                #[cfg(feature = "debug-track")]
+                self.arena.set_last_boundary(slice.as_ptr() as usize, new_rounded);
+                let data = &mut self.arena.head.data;
+                unsafe {
+                    let new_data_len = data.len() + (new_rounded - old_rounded);
+                    data.set_len(new_data_len);
+                    slice::from_raw_parts_mut(slice.as_mut_ptr(), new_len)
+                }
+            } else {
+                let grown = self.alloc_slice_raw(new_len);
+                grown[..old_len].copy_from_slice(slice);
+                grown
+            }
+        };
+        for dest in grown[old_len..].iter_mut() {
+            *dest = Default::default();
+        }
+        grown
+    }
+
+    /// Cut a slice down to its first `new_len` elements.
+    ///
+    /// If `slice` was the most recent allocation, the space past its new
+    /// end is given back for the next allocation, so a buffer can be
+    /// allocated at its worst-case size and trimmed once the real size is
+    /// known. Otherwise the cut-off tail is wasted.
+    pub fn shrink_last<T: Copy>(&mut self, slice: &'a mut [T], new_len: usize) -> &'a mut [T] {
+        assert!(new_len <= slice.len(), "Arena slice can't shrink to a larger length");
+        let granularity = self.arena.config.granularity;
+        let data = &mut self.arena.head.data;
+        let tip = data.as_ptr() as usize + data.len();
+        let end = slice.as_ptr() as usize + mem::size_of_val(slice);
+        let in_head = slice.as_ptr() as usize >= data.as_ptr() as usize;
+        if in_head && end == tip && !slice.is_empty() {
+            self.arena.note_peak();
+            let data = &mut self.arena.head.data;
+            let start = slice.as_ptr() as usize - data.as_ptr() as usize;
+            let kept = new_len * mem::size_of::<T>();
+            let kept = kept + padding_for(kept, granularity);
+            unsafe { data.set_len(cmp::min(start + kept, data.len())); }
+            #[cfg(feature = "debug-track")]
+            self.arena.set_last_boundary(slice.as_ptr() as usize, new_len * mem::size_of::<T>());
+        }
+        &mut slice[..new_len]
+    }
+
+    /// Copy a slice, keeping it inline if it has at most `N` elements and
+    /// only allocating it in the arena if it is longer.
+    ///
+    /// This spares the arena from many tiny allocations when most slices
+    /// are small.
+    pub fn alloc_slice_or_inline<T: Copy, const N: usize>(&mut self, elems: &[T])
+        -> SmallArenaSlice<'a, T, N>
+    {
+        if elems.len() <= N {
+            SmallArenaSlice::inline(elems)
+        } else {
+            SmallArenaSlice::spilled(self.alloc_slice(elems))
+        }
+    }
+
+    /// Allocate and populate a slice, creating each element as a function
+    /// of its index.
+    pub fn alloc_slice_fn<T: Copy, F>(&mut self, len: usize, mut f: F)-> &'a mut [T]
+        where F: FnMut(usize) -> T
+    {
+        let slice = self.alloc_slice_raw(len);
+        for (idx, dest) in slice.iter_mut().enumerate() {
+            *dest = f(idx)
+        }
+        slice
+    }
+
+    /// Allocate a slice populated by calling `f` on each index, like
+    /// `alloc_slice_fn`, but filled in parallel by rayon's thread pool.
+    /// Only the fill is parallel; the allocation happens on this thread.
+    #[cfg(feature = "rayon")]
+    pub fn alloc_slice_par_fn<T, F>(&mut self, len: usize, f: F) -> &'a mut [T]
+        where T: Copy + Send, F: Fn(usize) -> T + Sync
+    {
+        use rayon::prelude::*;
+
+        let slice = self.alloc_slice_raw(len);
+        slice.par_iter_mut().enumerate().for_each(|(idx, dest)| *dest = f(idx));
+        slice
+    }
+
+    /// Allocate a slice populated by default-valued elements.
+    pub fn alloc_slice_default<T: Copy+Default>(&mut self, len: usize)-> &'a mut [T] {
+        self.alloc_slice_fn(len, |_| Default::default())
+    }
+
+    /// Allocate an array of `num_bits` bits, all clear, packed eight to a
+    /// byte.
+    pub fn alloc_bitset(&mut self, num_bits: usize) -> ArenaBitset<'a> {
+        let byte_count = num_bits.div_ceil(8);
+        ArenaBitset::new(self.alloc_slice_default(byte_count), num_bits)
+    }
+
+    /// Allocate a record of `len` default-valued elements, to be filled in
+    /// through the returned builder. The builder tracks which elements were
+    /// set, so `finish_required()` can catch a field that was forgotten.
+    pub fn alloc_record<T: Copy+Default>(&mut self, len: usize) -> RecordBuilder<'a, T> {
+        RecordBuilder::new(self.alloc_slice_default(len))
+    }
+
+    /// Make sure the current chunk has at least `additional` free bytes,
+    /// adding a chunk if it doesn't.
+    ///
+    /// Allocations of up to that many bytes (less any alignment padding)
+    /// then won't need to grow the arena.
+    pub fn reserve(&mut self, additional: usize) {
+        self.try_reserve(additional).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `reserve`, but fails instead of panicking if adding the chunk
+    /// would exceed the arena's budget.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        let data = &self.arena.head.data;
+        if data.capacity() - data.len() < additional {
+            self.arena.grow(additional, 1)?;
+        }
+        Ok(())
+    }
+
+    /// Get how many bytes allocating a `T` would use up right now: its size
+    /// plus the padding needed to align it at the current position.
+    ///
+    /// This assumes the `T` fits in the current chunk; if it doesn't, it
+    /// goes at the start of a new one instead.
+    pub fn projected_cost<T>(&self) -> usize {
+        let granularity = self.arena.config.granularity;
+        let size = mem::size_of::<T>();
+        let size = size + padding_for(size, granularity);
+        let align = cmp::max(mem::align_of::<T>(), granularity);
+        let data = &self.arena.head.data;
+        padding_for(data.as_ptr() as usize + data.len(), align) + size
+    }
+
+    /// Whether a slice of `len` `T`s would fit in the current chunk, padding
+    /// included, so allocating it wouldn't grow the arena. A slice over the
+    /// arena's `max_slice_len` or `max_single_allocation` never fits.
+    pub fn slice_fits<T>(&self, len: usize) -> bool {
+        if len > self.arena.config.max_slice_len {
+            return false;
+        }
+        let granularity = self.arena.config.granularity;
+        let size = match mem::size_of::<T>().checked_mul(len) {
+            Some(size) if size <= self.arena.config.max_single_allocation => {
+                size.saturating_add(padding_for(size, granularity))
+            }
+            _ => { return false; }
+        };
+        let align = cmp::max(mem::align_of::<T>(), granularity);
+        let data = &self.arena.head.data;
+        let padding = padding_for(data.as_ptr() as usize + data.len(), align);
+        let free = data.capacity() - data.len();
+        padding <= free && size <= free - padding
+    }
+
+    /// Get the largest allocation, in bytes, aligned to `align` that would
+    /// fit in the current chunk without growing the arena. Unlike the
+    /// chunk's plain free space, this leaves out the padding needed to
+    /// reach that alignment.
+    pub fn max_contiguous(&self, align: usize) -> usize {
+        assert!(align.is_power_of_two(), "Arena alignment must be a power of two");
+        let granularity = self.arena.config.granularity;
+        let align = cmp::max(align, granularity);
+        let data = &self.arena.head.data;
+        let padding = padding_for(data.as_ptr() as usize + data.len(), align);
+        let free = (data.capacity() - data.len()).saturating_sub(padding);
+        free - free % granularity
+    }
+
+    /// Forbid the arena from growing until `unfreeze()`, e.g. to make sure
+    /// a real-time section never allocates from the heap.
+    ///
+    /// While frozen, allocations that fit in the current chunk succeed as
+    /// usual, but ones that would add a chunk fail: the `try_` methods
+    /// return `AllocError::Frozen`, and the rest panic. The arena stays
+    /// frozen after this allocator is gone.
+    pub fn freeze(&mut self) {
+        self.arena.frozen = true;
+    }
+
+    /// Allow the arena to grow again after `freeze()`.
+    pub fn unfreeze(&mut self) {
+        self.arena.frozen = false;
+    }
+
+    /// Remember how far the arena has been filled, so `pop_mark()` can
+    /// later discard everything allocated since.
+    ///
+    /// Marks nest: each `pop_mark()` goes back to the most recent mark not
+    /// yet popped. A `reset()` forgets every mark.
+    pub fn push_mark(&mut self) {
+        let arena = &mut *self.arena;
+        arena.marks.push(Mark {
+            chunk: arena.head.data.as_ptr() as usize,
+            len: arena.head.data.len(),
+            drops: arena.drops.len(),
+            arena_drops: arena.arena_drops.len(),
+        });
+    }
+
+    /// Discard everything allocated since the most recent `push_mark()`,
+    /// and forget that mark. Destructors registered since run, and chunks
+    /// added since are released. Panics if there is no mark.
+    ///
+    /// # Safety
+    ///
+    /// Nothing allocated after the mark may be used again: references to
+    /// it still type-check, but the memory will be handed out again.
+    pub unsafe fn pop_mark(&mut self) {
+        let arena = &mut *self.arena;
+        let mark = arena.marks.pop().expect("Arena has no mark to pop");
+        arena.note_peak();
+        arena.rewind(mark);
+        arena.bump_generation();
+    }
+
+    /// Look at the last `n` bytes allocated in the current chunk, for
+    /// checking what was just written.
+    ///
+    /// If the current chunk holds fewer than `n` bytes, all of them are
+    /// returned.
+    pub fn last_bytes(&self, n: usize) -> &[u8] {
+        let data = self.arena.head.data.filled();
+        &data[data.len() - cmp::min(n, data.len())..]
+    }
+
+    /// Borrow the whole unused remainder of the current chunk, without
+    /// allocating any of it.
+    ///
+    /// This suits encoders that don't know how much they will write until
+    /// they have written it: write into the front of the returned slice,
+    /// then `commit()` the bytes actually used. The borrow ends before
+    /// `commit()` can be called, so nothing else can be allocated between
+    /// the two; allocating anything else in between means `commit()` will
+    /// keep different bytes than the ones written.
+    ///
+    /// The slice may be empty if the current chunk is full.
+    pub fn claim_remaining(&mut self) -> &mut [u8] {
+        let data = &mut self.arena.head.data;
+        let len = data.len();
+        let free = data.capacity() - len;
+        // Chunks are filled when created, so the spare capacity is initialized.
+        unsafe { slice::from_raw_parts_mut(data.as_mut_ptr().add(len), free) }
+    }
+
+    /// Allocate the first `used` bytes of the region returned by the last
+    /// `claim_remaining()`, keeping whatever was written there.
+    ///
+    /// Panics if `used` is more than `claim_remaining()` offered.
+    pub fn commit(&mut self, used: usize) -> &'a mut [u8] {
+        let data = &mut self.arena.head.data;
+        let len = data.len();
+        assert!(used <= data.capacity() - len, "Arena commit exceeds the claimed region");
+        self.arena.allocation_count += 1;
+        #[cfg(feature = "debug-track")]
+        self.arena.boundaries.push((data.as_ptr() as usize + len, used));
+        unsafe {
+            data.set_len(len + used);
+            slice::from_raw_parts_mut(data.as_mut_ptr().add(len), used)
+        }
+    }
+
+    /// Allocate `fields` default-valued arrays of `len` elements each, for
+    /// a struct-of-arrays layout.
+    ///
+    /// The arrays are laid out back to back in one allocation, in the order
+    /// returned, so they share a chunk and stay close in cache.
+    pub fn alloc_soa<T: Copy+Default>(&mut self, len: usize, fields: usize) -> Vec<&'a mut [T]> {
+        let total = len.checked_mul(fields).expect("Arena slice size overflow");
+        let mut rest = self.alloc_slice_default(total);
+        let mut arrays = Vec::with_capacity(fields);
+        for _ in 0..fields {
+            let (array, remainder) = rest.split_at_mut(len);
+            arrays.push(array);
+            rest = remainder;
+        }
+        arrays
+    }
+
+    /// Allocate two default-valued slices of `len` elements each, with the
+    /// base of each aligned to `align`. This is convenient for
+    /// double-buffering, where both buffers need the same alignment
+    /// guarantee (e.g. page alignment).
+    ///
+    /// `align` must be a power of two. An `align` smaller than `T`'s own
+    /// alignment is raised to it.
+    pub fn alloc_two_aligned<T: Copy+Default>(&mut self, len: usize, align: usize)
+        -> (&'a mut [T], &'a mut [T])
+    {
+        assert!(align.is_power_of_two(), "Arena alignment must be a power of two");
+        self.check_alignment(align).unwrap_or_else(|e| panic!("{}", e));
+        let align = cmp::max(align, mem::align_of::<T>());
+
+        let first = self.alloc_slice_raw_aligned(len, align);
+        let second = self.alloc_slice_raw_aligned(len, align);
+        for dest in first.iter_mut().chain(second.iter_mut()) {
+            *dest = Default::default();
+        }
+        (first, second)
+    }
+
+    /// Allocate a default-valued buffer of `len` elements aligned to
+    /// `align`, to be cleared with `ScratchBuffer::reset_contents()` and
+    /// reused on each pass of a loop rather than allocated again.
+    ///
+    /// `align` must be a power of two. An `align` smaller than `T`'s own
+    /// alignment is raised to it.
+    pub fn scratch<T: Copy+Default>(&mut self, len: usize, align: usize) -> ScratchBuffer<'a, T> {
+        assert!(align.is_power_of_two(), "Arena alignment must be a power of two");
+        self.check_alignment(align).unwrap_or_else(|e| panic!("{}", e));
+        let align = cmp::max(align, mem::align_of::<T>());
+        let mut buffer = ScratchBuffer::new(self.alloc_slice_raw_aligned(len, align));
+        buffer.reset_contents();
+        buffer
+    }
+
+    /// Get the arena's capacity, as `Arena::capacity()` reports it.
+    pub fn capacity(&self) -> usize {
+        self.arena.capacity()
+    }
+
+    /// Get the bytes allocated so far, as `Arena::bytes_used()` reports
+    /// them.
+    pub fn bytes_used(&self) -> usize {
+        self.arena.bytes_used()
+    }
+
+    /// Get the number of chunks, as `Arena::chunk_count()` reports it.
+    pub fn chunk_count(&self) -> usize {
+        self.arena.chunk_count()
+    }
+}
+
+
+#[test]
+fn construct_simple() {
+    let mut arena = Arena::with_capacity(4);
+    let mut allocator = arena.allocator();
+
+    let x: &mut i32 = allocator.alloc(44);
+    let y: &mut u8 = allocator.alloc(3);
+    let z: &mut u32 = allocator.alloc(0x11223344);
+    let w: &mut f64 = allocator.alloc_default();
+    assert_eq!(*x, 44);
+    assert_eq!(*y, 3);
+    assert_eq!(*z, 0x11223344);
+    assert_eq!(*w, 0.0);
+}
+
+#[test]
+fn just_enough_capacity() {
+    let mut arena = Arena::with_capacity(7);
+    arena.allocator().alloc_slice_default::<u8>(7);
+
+    assert!(arena.head.next.is_none());
+    assert_eq!(arena.head.data.len(), 7);
+}
+
+#[test]
+fn barely_too_little_capacity() {
+    let mut arena = Arena::with_capacity(7);
+    arena.allocator().alloc_slice_default::<u8>(8);
+
+    assert!(arena.head.next.is_some());
+    assert_eq!(arena.head.data.len(), 8);
+}
+
+#[test]
+fn many_u8s() {
+    let mut arena = Arena::with_capacity(52);
+    for i in 0..100 {
+        assert_eq!(*arena.allocator().alloc(4), 4u8);
+        assert_eq!(arena.head.data.len(), (i % 52) + 1);
+    }
+    assert!(arena.head.data.capacity() > 52);
+}
+
+
+#[test]
+fn zero_capacity() {
+    let mut arena = Arena::with_capacity(0);
+    assert_eq!(arena.head.data.capacity(), 0);
+
+    assert_eq!(*arena.allocator().alloc(9u32), 9u32);
+
+    assert!(arena.head.data.capacity()>=4);
+    assert!(arena.head.data.len()==4);
+}
+
+#[test]
+fn surprisingly_large() {
+    // Make sure asking for an allocation that is larger than the
+    // block we otherwise would have allocated works fine.
+    let mut arena = Arena::with_capacity(10);
+
+    {
+        let mut allocator = arena.allocator();
+        let x = allocator.alloc(7u32);
+        let ys : &[u8] = allocator.alloc_slice_default(600);
+
+        assert_eq!(*x, 7u32);
+        assert_eq!(ys.len(), 600);
+    }
+
+    assert!(arena.head.next.is_some());
+    assert!(arena.head.data.capacity() >= 600);
+    assert_eq!(arena.head.data.len(), 600);
+}
+
+#[test]
+fn construct_slices() {
+    let mut arena = Arena::with_capacity(4);
+    let mut allocator = arena.allocator();
+
+    let s = ::std::str::from_utf8(allocator.alloc_slice(b"abc")).unwrap();
+    let xs: &[i32] = allocator.alloc_slice_fn(10, |idx| (idx as i32)*7);
+    let ys: &[u64] = allocator.alloc_slice_default(4);
+
+    assert_eq!(xs[9], 9*7);
+    assert_eq!(s, "abc");
+    assert_eq!(ys[0], 0);
+}
+
+#[test]
+fn zero_size() {
+    let mut arena = Arena::with_capacity(4);
+    {
+        let mut allocator = arena.allocator();
+
+        let many_units = allocator.alloc_slice_fn(500, |_| () );
+        assert_eq!(many_units.len(), 500);
+    }
+    assert_eq!(arena.capacity(), 4);
+
+    {
+        let mut allocator = arena.allocator();
+
+        let many_units: Vec<&mut ()> = (0..500).map(|_| allocator.alloc(())).collect();
+        assert_eq!(many_units.len(), 500);
     }
     assert_eq!(arena.capacity(), 4);
+}
+
+#[test]
+fn two_aligned() {
+    let mut arena = Arena::with_capacity(10);
+    let mut allocator = arena.allocator();
+
+    allocator.alloc(1u8);
+    let (a, b): (&mut [u32], &mut [u32]) = allocator.alloc_two_aligned(100, 4096);
+
+    assert_eq!(a.len(), 100);
+    assert_eq!(b.len(), 100);
+    assert_eq!(a.as_ptr() as usize % 4096, 0);
+    assert_eq!(b.as_ptr() as usize % 4096, 0);
+    assert!(a.iter().chain(b.iter()).all(|&x| x == 0));
+
+    let a_range = a.as_ptr() as usize..(a.as_ptr() as usize + 400);
+    assert!(!a_range.contains(&(b.as_ptr() as usize)));
+    assert!(!a_range.contains(&(b.as_ptr() as usize + 399)));
+}
+
+#[test]
+fn cached_capacity() {
+    let mut arena = Arena::with_capacity(16);
+    assert_eq!(arena.capacity(), arena.walked_capacity());
+    {
+        let mut allocator = arena.allocator();
+        for i in 0..200u64 {
+            allocator.alloc(i);
+        }
+        allocator.alloc_slice_default::<u8>(5000);
+    }
+    assert!(arena.head.next.is_some());
+    assert_eq!(arena.capacity(), arena.walked_capacity());
+}
+
+#[test]
+fn indexed() {
+    #[derive(Copy, Clone)]
+    struct Node {
+        id: usize,
+        _payload: [u8; 5],
+    }
+
+    let mut arena = Arena::with_capacity(16);
+    {
+        let mut allocator = arena.allocator();
+        for expected in 0..50 {
+            let (node, index) = allocator.alloc_indexed(|id| Node { id, _payload: [0; 5] });
+            assert_eq!(index, expected);
+            assert_eq!(node.id, expected);
+        }
+    }
+    assert!(arena.head.next.is_some());
+
+    let (_, index) = arena.allocator().alloc_indexed(|id| id);
+    assert_eq!(index, 50);
+}
+
+#[test]
+fn larger_than_any_chunk() {
+    #[derive(Copy, Clone)]
+    struct Big {
+        bytes: [u8; 4096],
+    }
+
+    let mut arena = Arena::with_capacity(16);
+    {
+        let big = arena.allocator().alloc(Big { bytes: [7; 4096] });
+        assert!(big.bytes.iter().all(|&b| b == 7));
+    }
+
+    // Exactly one chunk was added, and it is big enough on its own.
+    let old_head = arena.head.next.as_ref().unwrap();
+    assert!(old_head.next.is_none());
+    assert_eq!(old_head.data.len(), 0);
+    assert!(arena.head.data.capacity() >= 4096);
+    assert_eq!(arena.head.data.len(), 4096);
+}
+
+#[test]
+fn content_hash() {
+    use std::collections::hash_map::DefaultHasher;
+
+    fn fill(arena: &mut Arena, last: u32) {
+        let mut allocator = arena.allocator();
+        allocator.alloc(3u8);
+        allocator.alloc(0x1234_5678u32);
+        allocator.alloc_slice(b"a longer run of bytes that spills out of the first chunk");
+        allocator.alloc(last);
+    }
+
+    let mut hashes = Vec::new();
+    for &last in &[9, 9, 10] {
+        let mut arena = Arena::with_capacity(16);
+        fill(&mut arena, last);
+        assert_eq!(arena.chunks().count(), 2);
+
+        let mut hasher = DefaultHasher::new();
+        arena.content_hash(&mut hasher);
+        hashes.push(hasher.finish());
+    }
+
+    assert_eq!(hashes[0], hashes[1]);
+    assert!(hashes[0] != hashes[2]);
+}
+
+#[test]
+fn chunks_in_allocation_order() {
+    let mut arena = Arena::with_capacity(4);
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc_slice(b"abcd");
+        allocator.alloc_slice(b"efg");
+    }
+    let chunks: Vec<&[u8]> = arena.chunks().collect();
+    assert_eq!(chunks, vec![&b"abcd"[..], &b"efg"[..]]);
+}
+
+#[test]
+fn slice_uninit() {
+    let mut arena = Arena::with_capacity(4);
+    let mut allocator = arena.allocator();
+
+    let xs = allocator.alloc_slice_uninit::<u32>(3);
+    for (idx, x) in xs.iter_mut().enumerate() {
+        *x = MaybeUninit::new(idx as u32 * 2);
+    }
+    let xs: Vec<u32> = xs.iter().map(|x| unsafe { x.assume_init() }).collect();
+    assert_eq!(xs, vec![0, 2, 4]);
+}
+
+#[cfg(feature = "debug-poison")]
+#[test]
+fn poisoned_chunks() {
+    let mut arena = Arena::with_capacity(8);
+    let mut allocator = arena.allocator();
+
+    allocator.alloc(1u8);
+    let fresh = allocator.alloc_slice_uninit::<u8>(64);
+    assert!(fresh.iter().all(|b| unsafe { b.assume_init() } == 0xCD));
+    let written = allocator.alloc_slice_default::<u8>(4);
+    assert_eq!(written, &[0, 0, 0, 0]);
+}
+
+#[test]
+fn slice_or_inline() {
+    let mut arena = Arena::with_capacity(4);
+    {
+        let mut allocator = arena.allocator();
+        let mut small = allocator.alloc_slice_or_inline::<u16, 4>(&[1, 2, 3]);
+        assert!(small.is_inline());
+        small[0] = 10;
+        assert_eq!(&*small, &[10, 2, 3]);
+    }
+    assert_eq!(arena.head.data.len(), 0);
+    assert!(arena.head.next.is_none());
+
+    {
+        let mut allocator = arena.allocator();
+        let large = allocator.alloc_slice_or_inline::<u16, 4>(&[1, 2, 3, 4, 5]);
+        assert!(!large.is_inline());
+        assert_eq!(&*large, &[1, 2, 3, 4, 5]);
+    }
+    assert_eq!(arena.head.data.len(), 10);
+}
+
+#[test]
+fn claim_and_commit() {
+    let mut arena = Arena::with_capacity(32);
+    let mut allocator = arena.allocator();
+
+    allocator.alloc(1u8);
+    let written = {
+        let region = allocator.claim_remaining();
+        assert_eq!(region.len(), 31);
+        region[..5].copy_from_slice(b"hello");
+        5
+    };
+    let committed = allocator.commit(written);
+    assert_eq!(committed, b"hello");
+
+    assert_eq!(allocator.claim_remaining().len(), 26);
+    let next = allocator.alloc(b'!');
+    assert_eq!(*next, b'!');
+    assert_eq!(committed, b"hello");
+}
+
+#[test]
+#[should_panic]
+fn commit_too_much() {
+    let mut arena = Arena::with_capacity(8);
+    let mut allocator = arena.allocator();
+    allocator.claim_remaining();
+    allocator.commit(9);
+}
+
+#[test]
+fn alloc_by_reference() {
+    let mut arena = Arena::with_capacity(16);
+    let mut allocator = arena.allocator();
+
+    let mut page = [0u8; 4096];
+    for (idx, b) in page.iter_mut().enumerate() {
+        *b = idx as u8;
+    }
+    let copy = allocator.alloc_copy(&page);
+    assert!(copy[..] == page[..]);
+    assert!(copy.as_ptr() != page.as_ptr());
+}
+
+#[test]
+fn grow_and_reset() {
+    let mut arena = Arena::with_capacity(8);
+    arena.allocator().alloc(1u64);
+    assert!(!arena.has_grown());
+
+    arena.allocator().alloc(2u64);
+    assert!(arena.has_grown());
+
+    arena.reset();
+    assert!(arena.head.next.is_none());
+    assert_eq!(arena.head.data.len(), 0);
+    assert_eq!(arena.capacity(), arena.walked_capacity());
+    assert!(arena.has_grown());
+
+    let capacity = arena.capacity();
+    assert_eq!(*arena.allocator().alloc(3u64), 3);
+    assert_eq!(arena.capacity(), capacity);
+}
+
+#[test]
+fn slice_dedup() {
+    let mut arena = Arena::with_capacity(64);
+    {
+        let mut allocator = arena.allocator();
+
+        let deduped = allocator.alloc_slice_dedup(&[1u32, 1, 2, 2, 2, 3, 1]);
+        assert_eq!(deduped, &[1, 2, 3, 1]);
+        let empty = allocator.alloc_slice_dedup::<u32>(&[]);
+        assert!(empty.is_empty());
+    }
+
+    assert_eq!(arena.head.data.len(), 16);
+}
+
+#[test]
+fn grow_in_place() {
+    let mut arena = Arena::with_capacity(64);
+    let mut allocator = arena.allocator();
+
+    let xs = allocator.alloc_slice(&[1u32, 2, 3]);
+    let old_ptr = xs.as_ptr();
+    let xs = allocator.grow_slice(xs, 2);
+    assert_eq!(xs.as_ptr(), old_ptr);
+    assert_eq!(xs, &[1, 2, 3, 0, 0]);
+
+    let y = allocator.alloc(9u32);
+    assert_eq!(*y, 9);
+    assert_eq!(xs.len(), 5);
+}
+
+#[test]
+fn grow_by_relocating() {
+    let mut arena = Arena::with_capacity(64);
+    let mut allocator = arena.allocator();
+
+    // Not the most recent allocation.
+    let xs = allocator.alloc_slice(&[1u32, 2, 3]);
+    let y = allocator.alloc(9u32);
+    let old_ptr = xs.as_ptr();
+    let xs = allocator.grow_slice(xs, 1);
+    assert!(xs.as_ptr() != old_ptr);
+    assert_eq!(xs, &[1, 2, 3, 0]);
+    assert_eq!(*y, 9);
+
+    // No room left in the chunk.
+    let xs = allocator.grow_slice(xs, 100);
+    assert_eq!(xs.len(), 104);
+    assert_eq!(&xs[..4], &[1, 2, 3, 0]);
+}
+
+#[test]
+fn reproducible_offsets() {
+    fn build(arena: &mut Arena) -> Vec<RelOffset> {
+        let mut allocator = arena.allocator();
+        vec![
+            allocator.alloc_offset(1u8),
+            allocator.alloc_offset(2u64),
+            allocator.alloc_offset([3u16; 5]),
+            allocator.alloc_offset(4u32),
+            allocator.alloc_offset(5u64),
+        ]
+    }
+
+    let mut first = Arena::with_capacity(16);
+    let mut second = Arena::with_capacity(16);
+    let offsets = build(&mut first);
+    assert_eq!(offsets, build(&mut second));
+    assert_eq!(offsets[..3], [RelOffset(0), RelOffset(8), RelOffset(16)]);
+    assert!(first.head.next.is_some());
+
+    unsafe {
+        assert_eq!(*first.resolve::<u8>(offsets[0]), 1);
+        assert_eq!(*first.resolve::<u64>(offsets[1]), 2);
+        assert_eq!(*first.resolve::<[u16; 5]>(offsets[2]), [3; 5]);
+        assert_eq!(*second.resolve::<u32>(offsets[3]), 4);
+        assert_eq!(*second.resolve::<u64>(offsets[4]), 5);
+    }
+}
+
+#[test]
+#[should_panic]
+fn resolve_out_of_range() {
+    let mut arena = Arena::with_capacity(16);
+    arena.allocator().alloc(1u32);
+    unsafe { arena.resolve::<u32>(RelOffset(4)); }
+}
+
+#[test]
+fn slice_no_cross() {
+    let mut arena = Arena::with_capacity(1 << 14);
+    let mut allocator = arena.allocator();
+
+    // Bring the fill pointer to just before a 4 KB boundary.
+    let tip = allocator.alloc(0u8) as *mut u8 as usize + 1;
+    let filler = (2 * 4096 - 10 - tip % 4096) % 4096;
+    allocator.alloc_slice_default::<u8>(filler);
+
+    let data = [7u8; 100];
+    let placed = allocator.alloc_slice_no_cross(&data, 4096);
+    let start = placed.as_ptr() as usize;
+    assert_eq!(start / 4096, (start + 99) / 4096);
+    assert_eq!(start % 4096, 0);
+    assert!(placed.iter().all(|&b| b == 7));
+
+    // Fits without crossing, so stays put.
+    let next = allocator.alloc_slice_no_cross(&[1u32, 2], 4096);
+    assert_eq!(next.as_ptr() as usize, start + 100);
+}
+
+#[test]
+fn granular_grow_keeps_tip_aligned() {
+    let mut arena = Arena::with_config(ArenaConfig { granularity: 16, ..Default::default() });
+    let mut allocator = arena.allocator();
+    let xs = allocator.alloc_slice(&[1u8; 3]);
+    let start = xs.as_ptr() as usize;
+    let xs = allocator.grow_slice(xs, 20);
+    assert_eq!(xs.as_ptr() as usize, start);
+    assert_eq!(xs.len(), 23);
+    assert_eq!(allocator.bytes_used(), 32);
+    assert_eq!(allocator.alloc(2u8) as *mut u8 as usize, start + 32);
+}
+
+#[test]
+fn granular_slice_no_cross() {
+    let mut arena = Arena::with_config(ArenaConfig {
+        initial_capacity: 1 << 14,
+        granularity: 16,
+        ..Default::default()
+    });
+    let mut allocator = arena.allocator();
+
+    // Bring the fill pointer to 27 bytes before a 4 KB boundary, off the
+    // granularity.
+    let tip = allocator.alloc(0u8) as *mut u8 as usize + 16;
+    let filler = (2 * 4096 - 48 - tip % 4096) % 4096;
+    allocator.alloc_slice_default::<u8>(filler);
+    allocator.claim_remaining();
+    allocator.commit(21);
+
+    // At the fill pointer it would fit, but rounded up to the granularity
+    // it crosses.
+    let placed = allocator.alloc_slice_no_cross(&[7u8; 20], 4096);
+    let start = placed.as_ptr() as usize;
+    assert_eq!(start % 4096, 0);
+}
+
+#[test]
+fn config_round_trip() {
+    let config = ArenaConfig { initial_capacity: 123, ..Default::default() };
+    let arena = Arena::with_config(config);
+    assert_eq!(arena.config(), config);
+    assert_eq!(arena.capacity(), 123);
+
+    let copy = Arena::with_config(arena.config());
+    assert_eq!(copy.config(), config);
+    assert_eq!(Arena::with_capacity(55).config().initial_capacity, 55);
+    assert_eq!(Arena::new().config(), ArenaConfig::default());
+}
+
+#[test]
+fn struct_of_arrays() {
+    let mut arena = Arena::with_capacity(16);
+    let mut allocator = arena.allocator();
+
+    let arrays = allocator.alloc_soa::<f32>(10, 3);
+    assert_eq!(arrays.len(), 3);
+    for (idx, array) in arrays.iter().enumerate() {
+        assert_eq!(array.len(), 10);
+        assert!(array.iter().all(|&x| x == 0.0));
+        if idx > 0 {
+            assert_eq!(arrays[idx - 1].as_ptr().wrapping_add(10), array.as_ptr());
+        }
+    }
+
+    let empty = allocator.alloc_soa::<f32>(0, 2);
+    assert_eq!(empty.len(), 2);
+    assert!(empty.iter().all(|array| array.is_empty()));
+}
+
+#[test]
+fn peek_last_bytes() {
+    let mut arena = Arena::with_capacity(16);
+    let mut allocator = arena.allocator();
+
+    assert!(allocator.last_bytes(4).is_empty());
+    allocator.alloc_slice(b"abc");
+    allocator.alloc(0x0102_0304u32.to_be());
+    assert_eq!(allocator.last_bytes(4), &[1, 2, 3, 4]);
+    assert_eq!(allocator.last_bytes(100), &[b'a', b'b', b'c', CHUNK_FILL, 1, 2, 3, 4]);
+    assert!(allocator.last_bytes(0).is_empty());
+}
+
+#[test]
+fn budget() {
+    let mut arena = Arena::with_budget(64, 256);
+    {
+        let mut allocator = arena.allocator();
+        assert!(allocator.try_alloc_slice(&[1u8; 64]).is_ok());
+        // Growth is trimmed to what the budget allows.
+        assert!(allocator.try_alloc_slice(&[2u8; 150]).is_ok());
+        assert_eq!(allocator.try_alloc_slice(&[3u8; 100]), Err(AllocError::BudgetExceeded));
+        assert!(allocator.alloc_slice_default::<u8>(42).iter().all(|&b| b == 0));
+    }
+    assert!(arena.capacity() <= 256);
+
+    assert_eq!(arena.allocator().try_alloc([0u64; 40]), Err(AllocError::BudgetExceeded));
+    assert_eq!(arena.capacity(), arena.walked_capacity());
+}
+
+#[test]
+#[should_panic(expected = "budget")]
+fn budget_panics() {
+    let mut arena = Arena::with_budget(16, 32);
+    arena.allocator().alloc([0u8; 64]);
+}
+
+#[test]
+fn branded_refs() {
+    #[derive(Copy, Clone)]
+    struct Node<'id> {
+        value: u32,
+        next: Option<BrandedRef<'id, Node<'id>>>,
+    }
+
+    let mut arena = Arena::with_capacity(8);
+    let total = arena.branded(|mut nodes| {
+        let tail = nodes.alloc(Node { value: 2, next: None });
+        let head = nodes.alloc(Node { value: 1, next: Some(tail) });
+        nodes.get_mut(tail).value = 40;
+
+        let mut total = 0;
+        let mut cursor = Some(head);
+        while let Some(r) = cursor {
+            total += nodes.get(r).value;
+            cursor = nodes.get(r).next;
+        }
+        total
+    });
+    assert_eq!(total, 41);
+    assert!(arena.head.next.is_some());
+}
+
+#[test]
+fn linked_list() {
+    #[derive(Copy, Clone)]
+    struct Node<'a> {
+        value: u32,
+        next: Option<&'a Node<'a>>,
+    }
+
+    let mut arena = Arena::with_capacity(16);
+    let mut allocator = arena.allocator();
+
+    // take_while() can't be iterated from the back.
+    let items = (1..).take_while(|&value| value < 6).map(|value| Node { value, next: None });
+    let head = allocator.alloc_linked(items, |node, next| node.next = next);
+
+    let mut values = Vec::new();
+    let mut cursor = head;
+    while let Some(node) = cursor {
+        values.push(node.value);
+        cursor = node.next;
+    }
+    assert_eq!(values, vec![1, 2, 3, 4, 5]);
+
+    let empty = allocator.alloc_linked(Vec::<Node>::new(), |node, next| node.next = next);
+    assert!(empty.is_none());
+}
+
+#[test]
+fn granularity() {
+    let mut arena = Arena::with_granularity(16);
+    assert_eq!(arena.granularity(), 16);
+    {
+        let mut allocator = arena.allocator();
+        let a = allocator.alloc(1u8) as *mut u8 as usize;
+        let b = allocator.alloc(2u16) as *mut u16 as usize;
+        let c = allocator.alloc_slice(&[3u8; 17]).as_ptr() as usize;
+        let d = allocator.alloc(4u32) as *mut u32 as usize;
+        assert_eq!(a % 16, 0);
+        assert_eq!(b - a, 16);
+        assert_eq!(c - b, 16);
+        assert_eq!(d - c, 32);
+    }
+    assert_eq!(arena.head.data.len() % 16, 0);
+}
+
+#[test]
+fn closures() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let mut arena = Arena::with_capacity(16);
+    {
+        let mut allocator = arena.allocator();
+        let mut handlers = Vec::new();
+        for step in 1..4 {
+            let counter = counter.clone();
+            handlers.push(allocator.alloc_fn(move || { counter.fetch_add(step, Ordering::SeqCst); }));
+        }
+        for handler in handlers.iter_mut() {
+            handler();
+        }
+        handlers[2]();
+    }
+    assert_eq!(counter.load(Ordering::SeqCst), 1 + 2 + 3 + 3);
+    assert_eq!(Arc::strong_count(&counter), 4);
+
+    drop(arena);
+    assert_eq!(Arc::strong_count(&counter), 1);
+}
+
+#[test]
+fn closures_dropped_on_reset() {
+    use std::sync::Arc;
+
+    let captured = Arc::new(());
+    let mut arena = Arena::with_capacity(16);
+    {
+        let captured = captured.clone();
+        arena.allocator().alloc_fn(move || { let _ = &captured; });
+    }
+    assert_eq!(Arc::strong_count(&captured), 2);
+    arena.reset();
+    assert_eq!(Arc::strong_count(&captured), 1);
+}
+
+#[test]
+fn arena_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Arena>();
+}
+
+#[test]
+fn pow2_blocks() {
+    let mut arena = Arena::with_capacity(256);
+    {
+        let mut allocator = arena.allocator();
+        let a = allocator.alloc_pow2([1u8; 3]) as *mut [u8; 3] as usize;
+        let b = allocator.alloc_pow2([2u8; 5]) as *mut [u8; 5] as usize;
+        let c = allocator.alloc_pow2([3u8; 9]) as *mut [u8; 9] as usize;
+        let d = allocator.alloc_pow2([4u8; 3]) as *mut [u8; 3] as usize;
+        assert_eq!(a % 4, 0);
+        assert_eq!(b % 8, 0);
+        assert_eq!(c % 16, 0);
+        // Each block ends where the next power-of-two boundary begins.
+        assert_eq!(d, c + 16);
+        assert!(b >= a + 4 && c >= b + 8);
+    }
+    let used = arena.head.data.len();
+    assert_eq!(arena.allocator().alloc_pow2(()), &());
+    assert_eq!(arena.head.data.len(), used + 1);
+}
+
+#[test]
+fn reserve_within_budget() {
+    let mut arena = Arena::with_budget(16, 1024);
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc(1u8);
+        assert_eq!(allocator.try_reserve(8), Ok(()));
+        assert_eq!(allocator.try_reserve(500), Ok(()));
+        assert!(allocator.claim_remaining().len() >= 500);
+        assert_eq!(allocator.try_reserve(2000), Err(AllocError::BudgetExceeded));
+    }
+    let capacity = arena.capacity();
+    assert!(capacity <= 1024);
+
+    // The reserved space is used without growing further.
+    arena.allocator().alloc_slice_default::<u8>(500);
+    assert_eq!(arena.capacity(), capacity);
+}
+
+#[test]
+fn slice_flatten() {
+    let mut arena = Arena::with_capacity(8);
+    let mut allocator = arena.allocator();
+
+    let text = "first line\nsecond\n\nlast";
+    let joined = allocator.alloc_slice_flatten(text.split('\n').map(|line| line.as_bytes()));
+    assert_eq!(joined, b"first linesecondlast");
+
+    let none = allocator.alloc_slice_flatten(Vec::<&[u32]>::new().into_iter());
+    assert!(none.is_empty());
+}
+
+#[cfg(feature = "debug-track")]
+#[test]
+fn allocation_report() {
+    let mut arena = Arena::with_capacity(16);
+    let lines = {
+        let mut allocator = arena.allocator();
+        let (_, first) = (allocator.alloc(5u32), line!());
+        let (_, second) = (allocator.alloc_slice(b"tracked"), line!());
+        [first, second]
+    };
+
+    let report = arena.allocation_report();
+    assert_eq!(report.len(), 2);
+    assert!(report[0].0.file().ends_with("lib.rs"));
+    assert_eq!(report[0].0.line(), lines[0]);
+    assert_eq!(report[0].1, 4);
+    assert_eq!(report[1].0.line(), lines[1]);
+    assert_eq!(report[1].1, 7);
+}
+
+#[cfg(feature = "debug-track")]
+#[test]
+fn allocations_yield_each_allocation() {
+    let mut arena = Arena::with_capacity(8);
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc(7u8);
+        allocator.alloc(0x0102_0304u32);
+        allocator.alloc_slice(b"spills over");
+    }
+    assert!(arena.chunk_count() > 1);
+    let allocations: Vec<&[u8]> = arena.allocations().collect();
+    assert_eq!(allocations.len(), 3);
+    assert_eq!(allocations[0], &[7]);
+    assert_eq!(allocations[1], &0x0102_0304u32.to_ne_bytes());
+    assert_eq!(allocations[2], b"spills over");
+
+    arena.reset();
+    assert_eq!(arena.allocations().count(), 0);
+}
+
+#[test]
+fn scoped_allocation() {
+    let mut arena = Arena::with_capacity(16);
+    let mut allocator = arena.allocator();
+
+    let kept = allocator.alloc(1u8);
+    for i in 0..3u32 {
+        let local = [i; 4];
+        let scratch = allocator.alloc_scoped(&local[..]);
+        assert_eq!(scratch[3], i);
+    }
+    *kept += 1;
+    assert_eq!(*kept, 2);
+}
+
+#[test]
+fn serialize_graph_drops_garbage() {
+    #[derive(Copy, Clone)]
+    struct Node {
+        value: u32,
+        next: Option<RelOffset>,
+        back: Option<RelOffset>,
+    }
+
+    impl Traverse for Node {
+        fn visit_links<F: FnMut(&mut RelOffset)>(&mut self, mut f: F) {
+            if let Some(ref mut next) = self.next { f(next); }
+            if let Some(ref mut back) = self.back { f(back); }
+        }
+    }
+
+    let mut arena = Arena::with_capacity(64);
+    let root = {
+        let mut allocator = arena.allocator();
+        let garbage = Node { value: 0, next: None, back: None };
+        let c = allocator.alloc_offset(Node { value: 3, next: None, back: None });
+        allocator.alloc_offset(garbage);
+        let b = allocator.alloc_offset(Node { value: 2, next: Some(c), back: None });
+        allocator.alloc_offset(Node { value: 99, next: Some(b), back: None });
+        let a = allocator.alloc_offset(Node { value: 1, next: Some(b), back: Some(c) });
+        allocator.alloc_offset(garbage);
+        a
+    };
+    let blob = unsafe { arena.serialize_graph::<Node>(&[root]) };
+    assert_eq!(blob.len(), 3 * mem::size_of::<Node>());
+
+    let reloaded = Arena::from_vec(blob);
+    unsafe {
+        let a = reloaded.resolve::<Node>(RelOffset(0));
+        let b = reloaded.resolve::<Node>(a.next.unwrap());
+        let c = reloaded.resolve::<Node>(b.next.unwrap());
+        assert_eq!((a.value, b.value, c.value), (1, 2, 3));
+        assert_eq!(a.back, b.next);
+        assert_eq!(c.next, None);
+    }
+}
+
+#[test]
+fn raw_placement() {
+    #[derive(Debug, PartialEq)]
+    struct Pair(u16, u64);
+
+    let mut arena = Arena::with_capacity(16);
+    let mut allocator = arena.allocator();
+    allocator.alloc(1u8);
+    let pair = unsafe {
+        let memory = allocator.alloc_raw(mem::size_of::<Pair>(), mem::align_of::<Pair>()) as *mut Pair;
+        assert_eq!(memory as usize % mem::align_of::<Pair>(), 0);
+        ptr::write(memory, Pair(3, 4));
+        &*memory
+    };
+    assert_eq!(*pair, Pair(3, 4));
+}
+
+#[test]
+fn shrink_last_reclaims_tail() {
+    let mut arena = Arena::with_capacity(2048);
+    let mut allocator = arena.allocator();
+
+    let buffer = allocator.alloc_slice_default::<u8>(1024);
+    buffer[..10].copy_from_slice(b"0123456789");
+    let result = allocator.shrink_last(buffer, 10);
+    assert_eq!(result, b"0123456789");
+
+    let next = allocator.alloc(7u8);
+    assert_eq!(next as *mut u8 as usize, result.as_ptr() as usize + 10);
+    assert!(arena.head.next.is_none());
+}
+
+#[test]
+fn shrink_not_last_wastes_tail() {
+    let mut arena = Arena::with_capacity(64);
+    let mut allocator = arena.allocator();
+
+    let first = allocator.alloc_slice(&[1u32, 2, 3, 4]);
+    let after = allocator.alloc(5u32) as *mut u32 as usize;
+    let first = allocator.shrink_last(first, 2);
+    assert_eq!(first, &[1, 2]);
+    assert_eq!(allocator.alloc(6u32) as *mut u32 as usize, after + 4);
+}
+
+#[test]
+fn mask_chunks_in_place() {
+    let mut arena = Arena::with_capacity(8);
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc_slice(b"abcdef");
+        allocator.alloc_slice(b"ghijklmnop");
+    }
+    assert!(arena.head.next.is_some());
+
+    for chunk in arena.chunks_mut() {
+        for byte in chunk.iter_mut() {
+            *byte ^= 0x20;
+        }
+    }
+    let contents: Vec<u8> = arena.chunks().flat_map(|chunk| chunk.iter().cloned()).collect();
+    assert_eq!(contents, b"ABCDEFGHIJKLMNOP");
+}
+
+#[test]
+fn padding_count() {
+    let mut arena = Arena::with_capacity(64);
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc(1u8);
+        allocator.alloc(2u64);
+        allocator.alloc(3u8);
+        allocator.alloc(4u64);
+    }
+    assert_eq!(arena.padding_bytes(), 7 + 7);
+
+    arena.reset();
+    arena.allocator().alloc(5u64);
+    assert_eq!(arena.padding_bytes(), 14);
+}
+
+#[test]
+fn reset_keeping_adds_up_chunks() {
+    let mut arena = Arena::with_chunks(&[1024, 256]);
+    arena.reset_keeping(2000);
+    assert_eq!(arena.capacity(), 1280);
+    assert_eq!(arena.chunk_count(), 2);
+    assert_eq!(arena.head.data.capacity(), 1024);
+
+    arena.reset_keeping(1100);
+    assert_eq!(arena.capacity(), 1024);
+    arena = Arena::with_chunks(&[1024, 256]);
+    arena.reset_keeping(300);
+    assert_eq!(arena.capacity(), 256);
+    arena.reset_keeping(10);
+    assert_eq!(arena.capacity(), 256);
+    assert_eq!(arena.capacity(), arena.walked_capacity());
+}
+
+#[test]
+fn reset_keeping_absorbed_chunks() {
+    let mut small = Arena::with_capacity(64);
+    let mut big = Arena::with_capacity(4096);
+    big.allocator().alloc([1u8; 100]);
+    small.absorb(big);
+
+    small.reset_keeping(5000);
+    assert_eq!(small.capacity(), 4160);
+    assert_eq!(small.head.data.capacity(), 4096);
+    small.allocator().alloc([0u8; 2000]);
+    assert_eq!(small.chunk_count(), 2);
+
+    small.reset_keeping(1000);
+    assert_eq!(small.capacity(), 64);
+    assert_eq!(small.chunk_count(), 1);
+}
+
+#[test]
+fn reset_keeping_shrinks() {
+    let mut arena = Arena::with_capacity(1000);
+    {
+        let mut allocator = arena.allocator();
+        while allocator.arena.capacity() < 10 << 20 {
+            allocator.alloc_slice_default::<u8>(1 << 16);
+        }
+    }
+
+    arena.reset_keeping(1 << 20);
+    assert!(arena.capacity() <= 1 << 20);
+    assert!(arena.capacity() > 1 << 19);
+    assert_eq!(arena.capacity(), arena.walked_capacity());
+    assert_eq!(*arena.allocator().alloc(3u32), 3);
+
+    // Nothing is small enough, so the only chunk stays.
+    arena.reset_keeping(10);
+    assert!(arena.capacity() > 1 << 19);
+}
+
+#[test]
+fn nonnull_ring() {
+    #[derive(Copy, Clone)]
+    struct Link {
+        value: u32,
+        next: Option<NonNull<Link>>,
+    }
+
+    let mut arena = Arena::with_capacity(16);
+    let mut allocator = arena.allocator();
+    let first = allocator.alloc_nonnull(Link { value: 0, next: None });
+    let mut last = first;
+    for value in 1..5 {
+        let link = allocator.alloc_nonnull(Link { value, next: None });
+        unsafe { (*last.as_ptr()).next = Some(link); }
+        last = link;
+    }
+    unsafe { (*last.as_ptr()).next = Some(first); }
+
+    let mut values = Vec::new();
+    let mut iter = first;
+    for _ in 0..7 {
+        unsafe {
+            values.push((*iter.as_ptr()).value);
+            iter = (*iter.as_ptr()).next.unwrap();
+        }
+    }
+    assert_eq!(values, [0, 1, 2, 3, 4, 0, 1]);
+}
+
+#[test]
+fn ensure_capacity_in_place() {
+    let mut arena = Arena::with_capacity(100);
+    arena.ensure_capacity(50);
+    assert_eq!(arena.capacity(), 100);
+
+    arena.ensure_capacity(5000);
+    assert!(arena.capacity() >= 5000);
+    assert!(arena.head.next.is_none());
+    assert!(!arena.has_grown());
+    assert_eq!(arena.capacity(), arena.walked_capacity());
+
+    arena.allocator().alloc(1u8);
+    arena.ensure_capacity(arena.capacity());
+    assert!(arena.head.next.is_some());
+    assert_eq!(arena.capacity(), arena.walked_capacity());
+}
+
+#[test]
+fn slice_as_wrapper() {
+    use std::ops::Deref;
+
+    struct Word<'a>(&'a mut [u8]);
+
+    impl<'a> From<&'a mut [u8]> for Word<'a> {
+        fn from(bytes: &'a mut [u8]) -> Word<'a> { Word(bytes) }
+    }
+
+    impl<'a> Deref for Word<'a> {
+        type Target = [u8];
+        fn deref(&self) -> &[u8] { self.0 }
+    }
+
+    impl<'a> Word<'a> {
+        fn shout(&mut self) { self.0.make_ascii_uppercase(); }
+    }
+
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let mut word: Word = allocator.alloc_slice_as(b"arena");
+    word.shout();
+    assert_eq!(&*word, b"ARENA");
+}
+
+#[test]
+fn validate_after_operations() {
+    let mut arena = Arena::with_capacity(16);
+    assert_eq!(arena.validate(), Ok(()));
+
+    {
+        let mut allocator = arena.allocator();
+        for i in 0..100u64 {
+            allocator.alloc(i);
+        }
+        let slice = allocator.alloc_slice(&[1u8; 40]);
+        allocator.shrink_last(slice, 3);
+        let mut owned = String::new();
+        allocator.alloc_fn(move || owned.push('x'));
+    }
+    assert!(arena.head.next.is_some());
+    assert_eq!(arena.validate(), Ok(()));
+
+    arena.reset();
+    assert_eq!(arena.validate(), Ok(()));
+    arena.allocator().alloc_slice_default::<u8>(10000);
+    arena.reset_keeping(100);
+    assert_eq!(arena.validate(), Ok(()));
+    arena.ensure_capacity(5000);
+    assert_eq!(arena.validate(), Ok(()));
+
+    arena.total_capacity += 1;
+    assert!(arena.validate().is_err());
+}
+
+#[test]
+fn sorted_slices() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    assert_eq!(allocator.alloc_slice_sorted(&[3, 1, 2]), &[1, 2, 3]);
+    assert_eq!(allocator.alloc_slice_sorted_by(&[3, 1, 2], |a, b| b.cmp(a)), &[3, 2, 1]);
+}
+
+#[test]
+fn projected_cost_includes_padding() {
+    let mut arena = Arena::with_capacity(64);
+    let mut allocator = arena.allocator();
+    assert_eq!(allocator.projected_cost::<u64>(), 8);
+    allocator.alloc(1u8);
+    assert_eq!(allocator.projected_cost::<u8>(), 1);
+    assert_eq!(allocator.projected_cost::<u64>(), 7 + 8);
+
+    let before = allocator.arena.head.data.len();
+    allocator.alloc(2u64);
+    assert_eq!(allocator.arena.head.data.len() - before, 15);
+}
+
+#[test]
+fn strings() {
+    let mut arena = Arena::with_capacity(4);
+    let mut allocator = arena.allocator();
+    let greeting = allocator.alloc_str("hello");
+    let empty = allocator.alloc_str("");
+    greeting.make_ascii_uppercase();
+    assert_eq!((&*greeting, &*empty), ("HELLO", ""));
+}
+
+#[test]
+fn slice_from_raw() {
+    let source = [10u16, 20, 30, 40];
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let copy = unsafe { allocator.alloc_slice_from_raw(source.as_ptr().add(1), 3) };
+    assert_eq!(copy, &[20, 30, 40]);
+    assert!(copy.as_ptr() != source[1..].as_ptr());
+}
+
+#[test]
+fn slice_fits_at_boundary() {
+    let mut arena = Arena::with_capacity(64);
+    let mut allocator = arena.allocator();
+    allocator.alloc(1u8);
+    // 63 bytes are left, 56 of them once aligned for u64s.
+    assert!(allocator.slice_fits::<u8>(63));
+    assert!(!allocator.slice_fits::<u8>(64));
+    assert!(allocator.slice_fits::<u64>(7));
+    assert!(!allocator.slice_fits::<u64>(8));
+    assert!(!allocator.slice_fits::<u64>(usize::MAX));
+
+    allocator.alloc_slice_default::<u64>(7);
+    assert!(allocator.arena.head.next.is_none());
+    assert!(allocator.slice_fits::<u8>(0));
+    assert!(!allocator.slice_fits::<u8>(1));
+}
+
+#[test]
+fn slice_fits_respects_limits() {
+    let mut arena = Arena::with_config(ArenaConfig {
+        initial_capacity: 256,
+        max_slice_len: 16,
+        max_single_allocation: 32,
+        ..Default::default()
+    });
+    let allocator = arena.allocator();
+    assert!(allocator.slice_fits::<u8>(16));
+    assert!(!allocator.slice_fits::<u8>(17));
+    assert!(allocator.slice_fits::<u16>(16));
+    assert!(!allocator.slice_fits::<u32>(16));
+}
+
+#[test]
+fn max_contiguous_leaves_out_padding() {
+    let mut arena = Arena::with_capacity(64);
+    let mut allocator = arena.allocator();
+    assert_eq!(allocator.max_contiguous(8), 64);
+    allocator.alloc(1u8);
+    // 63 bytes are left, 56 of them once aligned for u64s.
+    assert_eq!(allocator.max_contiguous(1), 63);
+    assert_eq!(allocator.max_contiguous(8), 56);
+
+    allocator.alloc_slice_default::<u64>(6);
+    assert_eq!(allocator.max_contiguous(8), 8);
+    allocator.alloc(1u8);
+    assert!(allocator.arena.head.next.is_none());
+    assert_eq!(allocator.max_contiguous(1), 7);
+    assert_eq!(allocator.max_contiguous(8), 0);
+}
+
+#[test]
+fn frozen_arena_does_not_grow() {
+    let mut arena = Arena::with_capacity(64);
+    let mut allocator = arena.allocator();
+    allocator.freeze();
+    assert!(allocator.try_alloc_slice(&[1u8; 40]).is_ok());
+    assert_eq!(allocator.try_alloc_slice(&[2u8; 40]), Err(AllocError::Frozen));
+    assert_eq!(allocator.try_reserve(100), Err(AllocError::Frozen));
+    assert!(allocator.try_alloc(3u8).is_ok());
+    assert!(allocator.arena.head.next.is_none());
+
+    allocator.unfreeze();
+    assert!(allocator.try_alloc_slice(&[2u8; 40]).is_ok());
+    assert!(allocator.arena.head.next.is_some());
+}
+
+#[test]
+#[should_panic(expected = "frozen")]
+fn frozen_arena_panics() {
+    let mut arena = Arena::with_capacity(16);
+    let mut allocator = arena.allocator();
+    allocator.freeze();
+    allocator.alloc([0u8; 32]);
+}
+
+#[test]
+fn emit_offsets_are_cumulative() {
+    let mut arena = Arena::with_capacity(8);
+    let mut allocator = arena.allocator();
+    let (first, _) = allocator.emit(&[1u8, 2, 3]);
+    allocator.alloc(0u64);
+    let (second, jump) = allocator.emit(&[4u32, 0]);
+    let (third, _) = allocator.emit(b"ret");
+    assert_eq!((first, second, third), (0, 3, 11));
+
+    jump[1] = third as u32;
+    assert_eq!(jump, &[4, 11]);
+}
+
+#[test]
+fn every_slice_element_aligned() {
+    #[derive(Copy, Clone, Default)]
+    #[repr(align(32))]
+    struct Wide { _byte: u8 }
+
+    #[derive(Copy, Clone, Default)]
+    #[repr(C, align(16))]
+    struct Odd { _byte: u8, _short: u16 }
+
+    fn check<T: Copy + Default + 'static>(allocator: &mut Allocator) {
+        for len in 2..9 {
+            allocator.alloc(0u8);
+            let slice = allocator.alloc_slice_default::<T>(len);
+            for elem in slice.iter() {
+                assert_eq!(elem as *const T as usize % mem::align_of::<T>(), 0);
+            }
+        }
+    }
+
+    let mut arena = Arena::with_capacity(100);
+    let mut allocator = arena.allocator();
+    check::<Wide>(&mut allocator);
+    check::<Odd>(&mut allocator);
+    check::<u64>(&mut allocator);
+    check::<(u8, u32)>(&mut allocator);
+}
+
+#[test]
+fn uninit_array() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let slots = allocator.alloc_uninit_array::<u32, 4>();
+    for (i, slot) in slots.iter_mut().enumerate() {
+        *slot = MaybeUninit::new(i as u32 * 10);
+    }
+    let values = unsafe { assume_init(slots) };
+    assert_eq!(*values, [0, 10, 20, 30]);
+}
+
+#[test]
+fn preallocated_chunks() {
+    let mut arena = Arena::with_chunks(&[256, 1024]);
+    assert_eq!(arena.capacity(), 1280);
+    assert_eq!(arena.chunk_count(), 2);
+    assert_eq!(arena.head.data.capacity(), 256);
+    assert_eq!(arena.validate(), Ok(()));
+
+    arena.allocator().alloc([1u8; 200]);
+    assert_eq!(arena.chunk_count(), 2);
+    arena.allocator().alloc([2u8; 100]);
+    assert_eq!(arena.chunk_count(), 3);
+    assert_eq!(arena.validate(), Ok(()));
+    assert_eq!(arena.chunks().map(|chunk| chunk.len()).collect::<Vec<_>>(), [0, 200, 100]);
+}
+
+#[test]
+fn read_across_chunks() {
+    use std::io::Read;
+
+    let mut arena = Arena::with_capacity(5);
+    let mut expected = Vec::new();
+    {
+        let mut allocator = arena.allocator();
+        for word in &["one ", "two three ", "four", "", " five six seven"] {
+            allocator.alloc_slice(word.as_bytes());
+            expected.extend_from_slice(word.as_bytes());
+        }
+    }
+    assert!(arena.chunk_count() > 2);
+
+    let mut contents = Vec::new();
+    arena.reader().read_to_end(&mut contents).unwrap();
+    assert_eq!(contents, expected);
+
+    let mut small = [0u8; 3];
+    let mut reader = arena.reader();
+    reader.read_exact(&mut small).unwrap();
+    assert_eq!(&small, b"one");
+}
+
+#[test]
+fn slice_length_limit() {
+    let mut arena = Arena::with_config(ArenaConfig { max_slice_len: 16, ..Default::default() });
+    let mut allocator = arena.allocator();
+    assert!(allocator.try_alloc_slice(&[0u64; 16]).is_ok());
+    assert_eq!(allocator.try_alloc_slice(&[0u8; 17]), Err(AllocError::SliceTooLong));
+    assert_eq!(allocator.alloc(5u8), &5);
+}
+
+#[test]
+fn alignment_limit() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let x = allocator.alloc_aligned(5u16, 64);
+    assert_eq!(*x, 5);
+    assert_eq!(x as *mut u16 as usize % 64, 0);
+    assert!(allocator.try_alloc_aligned(1u8, PAGE_SIZE).is_ok());
+    assert_eq!(allocator.try_alloc_aligned(1u8, 1 << 30).err(), Some(AllocError::AlignmentTooLarge));
+
+    let mut relaxed = Arena::with_config(ArenaConfig { max_alignment: 1 << 16, ..Default::default() });
+    assert!(relaxed.allocator().try_alloc_aligned(1u8, 1 << 16).is_ok());
+}
+
+#[test]
+#[should_panic(expected = "alignment is larger than allowed")]
+fn alignment_limit_panics() {
+    let mut arena = Arena::with_config(ArenaConfig { max_alignment: 16, ..Default::default() });
+    arena.allocator().scratch::<u8>(4, 32);
+}
+
+#[test]
+#[should_panic(expected = "alignment is larger than allowed")]
+fn simd_alignment_limit_panics() {
+    let mut arena = Arena::with_config(ArenaConfig { max_alignment: 16, ..Default::default() });
+    arena.allocator().alloc_slice_simd(&[1.0f32; 8], 32);
+}
+
+#[test]
+#[should_panic(expected = "alignment is larger than allowed")]
+fn no_cross_alignment_limit_panics() {
+    let mut arena = Arena::with_config(ArenaConfig { max_alignment: 16, ..Default::default() });
+    arena.allocator().alloc_slice_no_cross(&[1u8; 4], 64);
+}
+
+#[test]
+fn single_allocation_limit() {
+    let mut arena = Arena::with_config(ArenaConfig {
+        initial_capacity: 64,
+        max_single_allocation: 32,
+        ..Default::default()
+    });
+    let mut allocator = arena.allocator();
+    assert!(allocator.try_alloc_slice(&[1u8; 32]).is_ok());
+    assert_eq!(allocator.try_alloc_slice(&[0u16; 17]), Err(AllocError::AllocationTooLarge));
+    assert_eq!(allocator.try_alloc([0u8; 100]).err(), Some(AllocError::AllocationTooLarge));
+    assert_eq!(allocator.alloc_slice(&[2u32; 8]), &[2; 8]);
+}
+
+#[test]
+#[should_panic(expected = "larger than allowed")]
+fn single_allocation_limit_panics() {
+    let mut arena = Arena::with_config(ArenaConfig { max_single_allocation: 8, ..Default::default() });
+    arena.allocator().alloc_slice(&[0u8; 9]);
+}
+
+#[test]
+#[should_panic(expected = "longer than allowed")]
+fn slice_length_limit_panics() {
+    let mut arena = Arena::with_config(ArenaConfig { max_slice_len: 4, ..Default::default() });
+    arena.allocator().alloc_slice_fn(5, |i| i);
+}
+
+#[test]
+fn join_two_halves() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let (joined, seam) = allocator.alloc_slice_join2(b"tail", b"head");
+    assert_eq!(seam, 4);
+    assert_eq!(joined, b"tailhead");
+    assert_eq!(&joined[seam..], b"head");
+
+    let (empty, seam) = allocator.alloc_slice_join2::<u8>(&[], &[]);
+    assert_eq!((empty.len(), seam), (0, 0));
+}
+
+#[test]
+fn unused_space_in_old_chunks() {
+    let mut arena = Arena::with_capacity(100);
+    assert_eq!(arena.total_unused(), 100);
+    arena.allocator().alloc([0u8; 90]);
+    assert_eq!(arena.total_unused(), 10);
+
+    arena.allocator().alloc([0u8; 20]);
+    let head_free = arena.head.data.capacity() - arena.head.data.len();
+    assert_eq!(arena.total_unused(), 10 + head_free);
+    assert_eq!(arena.reserved_bytes(), arena.capacity());
+    assert_eq!(arena.reserved_bytes(), 90 + 20 + arena.total_unused());
+}
+
+#[test]
+fn offset_between_allocations() {
+    let mut arena = Arena::with_capacity(16);
+    let (first, second, far) = {
+        let mut allocator = arena.allocator();
+        let first = allocator.alloc(1u32) as *const u32;
+        let second = allocator.alloc(2u32) as *const u32;
+        let far = allocator.alloc([3u8; 32]) as *const [u8; 32];
+        (first, second, far)
+    };
+    assert_eq!(arena.offset_between(first, second), Some(4));
+    assert_eq!(arena.offset_between(second, first), Some(-4));
+    assert_eq!(arena.offset_between(first, first), Some(0));
+    assert_eq!(arena.offset_between(first, far), None);
+    assert_eq!(arena.offset_between(&0u8, first), None);
+}
+
+#[test]
+fn contains_only_allocated_memory() {
+    let mut arena = Arena::with_capacity(64);
+    let (inside, end) = {
+        let mut allocator = arena.allocator();
+        let values = allocator.alloc_slice(&[1u32, 2, 3]);
+        (&values[1] as *const u32, values.as_ptr().wrapping_add(3))
+    };
+    let heap = Box::new(5u32);
+
+    assert!(arena.contains(inside));
+    assert!(!arena.contains(end));
+    assert!(!arena.contains(&*heap as *const u32));
+}
+
+#[test]
+fn whole_arena_into_box() {
+    let mut arena = Arena::with_capacity(64);
+    arena.allocator().alloc_slice(&[[1u8, 2, 3], [4, 5, 6]]);
+    let boxed: Box<[[u8; 3]]> = arena.try_into_boxed_slice().unwrap();
+    assert_eq!(&*boxed, &[[1, 2, 3], [4, 5, 6]]);
+
+    let mut arena = Arena::with_capacity(64);
+    arena.allocator().alloc_slice(&[1u8; 5]);
+    let arena = arena.try_into_boxed_slice::<[u8; 2]>().unwrap_err();
+    let arena = arena.try_into_boxed_slice::<u32>().unwrap_err();
+    assert_eq!(arena.bytes_used(), 5);
+
+    let mut arena = Arena::with_capacity(4);
+    arena.allocator().alloc_slice(&[1u8; 5]);
+    assert!(arena.try_into_boxed_slice::<u8>().is_err());
+}
+
+#[test]
+fn trace_every_allocation() {
+    use std::sync::{Arc, Mutex};
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut arena = Arena::with_capacity(64);
+    {
+        let seen = seen.clone();
+        arena.set_trace(move |size, align, offset| seen.lock().unwrap().push((size, align, offset)));
+    }
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc(1u8);
+        allocator.alloc(2u32);
+        allocator.alloc_slice(&[3u16; 5]);
+    }
+    assert_eq!(*seen.lock().unwrap(), [(1, 1, 0), (4, 4, 4), (10, 2, 8)]);
+}
+
+#[test]
+fn simd_padded_slices() {
+    let mut arena = Arena::with_capacity(256);
+    let mut allocator = arena.allocator();
+    allocator.alloc(1u8);
+    let floats = allocator.alloc_slice_simd(&[1.0f32; 10], 32);
+    assert_eq!(floats.as_ptr() as usize % 32, 0);
+    assert_eq!(floats, &[1.0; 10]);
+
+    // The last vector, starting at element 8, is fully inside the chunk.
+    let last_vector = floats.as_ptr() as usize + 32;
+    let next = allocator.alloc(2u8) as *mut u8 as usize;
+    assert!(next >= last_vector + 32);
+    assert!(allocator.arena.head.next.is_none());
+}
+
+#[test]
+fn deep_clone_is_independent() {
+    let mut arena = Arena::with_capacity(8);
+    let (first, second) = {
+        let mut allocator = arena.allocator();
+        (allocator.alloc_offset(7u32), allocator.alloc_offset([1u8; 12]))
+    };
+    let clone = arena.deep_clone();
+    assert_eq!(clone.chunk_count(), arena.chunk_count());
+    assert_eq!(clone.validate(), Ok(()));
+
+    for chunk in arena.chunks_mut() {
+        for byte in chunk.iter_mut() {
+            *byte = 0xFF;
+        }
+    }
+    unsafe {
+        assert_eq!(*clone.resolve::<u32>(first), 7);
+        assert_eq!(*clone.resolve::<[u8; 12]>(second), [1; 12]);
+        assert_eq!(*arena.resolve::<u32>(first), u32::MAX);
+    }
+}
+
+#[test]
+fn padded_to_cache_lines() {
+    let mut arena = Arena::with_capacity(1024);
+    let mut allocator = arena.allocator();
+    allocator.alloc(1u8);
+    let mut counters = allocator.alloc_slice_padded::<u64>(4, 64);
+    for index in 0..4 {
+        let counter = counters.get_mut(index).unwrap();
+        assert_eq!(*counter, 0);
+        assert_eq!(counter as *mut u64 as usize % 64, 0);
+        *counter = index as u64 + 1;
+    }
+    assert!(counters.get(4).is_none());
+    assert_eq!(format!("{:?}", counters), "[1, 2, 3, 4]");
+}
+
+#[test]
+fn large_allocation_grows_once() {
+    let mut arena = Arena::with_capacity(16);
+    let mut allocator = arena.allocator();
+    let big = allocator.alloc_slice_default::<u64>(1 << 17);
+    assert_eq!(big.len(), 1 << 17);
+    assert_eq!(allocator.arena.chunk_count(), 2);
+    assert_eq!(allocator.arena.allocation_count(), 1);
+    let head_capacity = allocator.arena.head.data.capacity();
+    assert!(head_capacity >= 8 << 17);
+    assert!(head_capacity <= 2 * ((8 << 17) + 7));
+}
+
+#[test]
+fn impossible_allocation_fails() {
+    let mut arena = Arena::with_capacity(16);
+    let mut allocator = arena.allocator();
+    assert_eq!(allocator.try_alloc_slice_raw_aligned::<u8>(usize::MAX - 2, 8).unwrap_err(),
+               AllocError::BudgetExceeded);
+    assert_eq!(allocator.try_alloc_slice_raw_aligned::<u8>(isize::MAX as usize + 1, 1).unwrap_err(),
+               AllocError::BudgetExceeded);
+    assert_eq!(allocator.arena.chunk_count(), 1);
+}
+
+#[test]
+fn absorb_other_arena() {
+    let mut first = Arena::with_capacity(16);
+    let mut second = Arena::with_capacity(16);
+    let a = first.allocator().alloc_slice(b"first ") as *const [u8];
+    let b = second.allocator().alloc_slice(b"second, grown past its chunk") as *const [u8];
+    let second_capacity = second.capacity();
+    let first_capacity = first.capacity();
+
+    first.absorb(second);
+    assert_eq!(first.capacity(), first_capacity + second_capacity);
+    assert_eq!(first.validate(), Ok(()));
+    assert!(first.contains(a as *const u8));
+    assert!(first.contains(b as *const u8));
+    unsafe { assert_eq!(&(&*b)[..6], b"second"); }
+
+    first.allocator().alloc_slice(b"third");
+    let contents: Vec<u8> = first.chunks().flat_map(|chunk| chunk.iter().cloned()).collect();
+    assert_eq!(contents, &b"second, grown past its chunkfirst third"[..]);
+}
+
+#[test]
+fn absorb_keeps_destructors() {
+    use std::sync::Arc;
 
+    let shared = Arc::new(());
+    let mut first = Arena::new();
+    {
+        let mut second = Arena::new();
+        let held = shared.clone();
+        second.allocator().alloc_fn(move || { let _ = &held; });
+        first.absorb(second);
+    }
+    assert_eq!(Arc::strong_count(&shared), 2);
+    drop(first);
+    assert_eq!(Arc::strong_count(&shared), 1);
+}
+
+#[test]
+fn numbered_allocations_count_up() {
+    let mut arena = Arena::with_capacity(16);
     {
         let mut allocator = arena.allocator();
+        assert_eq!(allocator.alloc_numbered(1u8).0, 0);
+        assert_eq!(allocator.alloc_numbered([7u64; 8]).0, 1);
+        allocator.alloc(3u32);
+        let (number, value) = allocator.alloc_numbered(5u16);
+        assert_eq!((number, *value), (2, 5));
+    }
 
-        let many_units: Vec<&mut ()> = (0..500).map(|_| allocator.alloc(())).collect();
-        assert_eq!(many_units.len(), 500);
+    arena.reset();
+    assert_eq!(arena.allocator().alloc_numbered(()).0, 3);
+    arena.reset_sequence();
+    assert_eq!(arena.allocator().alloc_numbered('x').0, 0);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_fill_matches_sequential() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let f = |idx: usize| (idx as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (idx as u64 >> 3);
+
+    let parallel = allocator.alloc_slice_par_fn(100_000, f);
+    let sequential = allocator.alloc_slice_fn(100_000, f);
+    assert_eq!(parallel, sequential);
+}
+
+#[test]
+fn fallible_construction() {
+    let arena = Arena::try_with_capacity(100).unwrap();
+    assert!(arena.capacity() >= 100);
+    assert_eq!(arena.config().initial_capacity, 100);
+
+    let config = ArenaConfig { initial_capacity: 64, granularity: 8, ..Default::default() };
+    let mut arena = Arena::try_with_config(config).unwrap();
+    assert_eq!(arena.config(), config);
+    assert_eq!(*arena.allocator().alloc(7u8), 7);
+
+    assert_eq!(Arena::try_with_capacity(usize::MAX).unwrap_err(), AllocError::OutOfMemory);
+}
+
+#[test]
+fn split_slice_halves() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let (frozen, open) = allocator.alloc_slice_split_mut(&[1u32, 2, 3], 2, 2);
+    assert_eq!(frozen, &[1, 2]);
+    assert_eq!(open, &[3, 0, 0]);
+    assert_eq!(unsafe { frozen.as_ptr().add(2) }, open.as_ptr());
+
+    open[0] = 30;
+    open[2] = 50;
+    assert_eq!(frozen, &[1, 2]);
+    assert_eq!(open, &[30, 0, 50]);
+
+    let (frozen, open) = allocator.alloc_slice_split_mut::<u8>(&[], 3, 0);
+    assert!(frozen.is_empty());
+    assert_eq!(open, &[0, 0, 0]);
+}
+
+#[test]
+fn overhead_scales_with_chunks() {
+    let mut arena = Arena::with_capacity(8);
+    let single = arena.overhead_bytes();
+    assert!(single >= mem::size_of::<Vec<u8>>());
+
+    for _ in 0..40 {
+        arena.allocator().alloc([0u8; 8]);
     }
-    assert_eq!(arena.capacity(), 4);
+    let chunks = arena.chunk_count();
+    assert!(chunks > 3);
+    let per_chunk = mem::size_of::<Chunk<Vec<u8>>>() + mem::size_of::<usize>();
+    assert_eq!(arena.overhead_bytes(), single + (chunks - 1) * per_chunk);
+
+    arena.reset();
+    assert_eq!(arena.overhead_bytes(), single);
+}
+
+#[test]
+fn checked_allocation_passes() {
+    let mut arena = Arena::new();
+    let range = arena.allocator().alloc_checked((3u32, 9u32), |&(lo, hi)| lo <= hi);
+    assert_eq!(*range, (3, 9));
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "failed its check")]
+fn checked_allocation_panics() {
+    let mut arena = Arena::new();
+    arena.allocator().alloc_checked((9u32, 3u32), |&(lo, hi)| lo <= hi);
+}
+
+#[test]
+fn nested_marks() {
+    let mut arena = Arena::with_capacity(32);
+    let mut allocator = arena.allocator();
+    allocator.alloc_slice(b"base");
+
+    allocator.push_mark();
+    allocator.alloc_slice(b"outer");
+    allocator.push_mark();
+    for _ in 0..20 {
+        allocator.alloc([7u8; 8]);
+    }
+    assert!(allocator.arena.chunk_count() > 1);
+
+    unsafe { allocator.pop_mark(); }
+    assert_eq!(allocator.arena.bytes_used(), 9);
+    assert_eq!(allocator.arena.chunk_count(), 1);
+    assert_eq!(allocator.arena.capacity(), 32);
+    assert_eq!(allocator.alloc_slice(b"!"), b"!");
+
+    unsafe { allocator.pop_mark(); }
+    assert_eq!(allocator.arena.bytes_used(), 4);
+    let contents: Vec<u8> = allocator.arena.chunks().flat_map(|chunk| chunk.iter().cloned()).collect();
+    assert_eq!(contents, b"base");
+}
+
+#[test]
+fn pop_mark_runs_destructors() {
+    use std::sync::Arc;
+
+    let shared = Arc::new(());
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let kept = shared.clone();
+    allocator.alloc_fn(move || { let _ = &kept; });
+    allocator.push_mark();
+    let discarded = shared.clone();
+    allocator.alloc_fn(move || { let _ = &discarded; });
+    assert_eq!(Arc::strong_count(&shared), 3);
+
+    unsafe { allocator.pop_mark(); }
+    assert_eq!(Arc::strong_count(&shared), 2);
+}
+
+#[test]
+fn pop_mark_after_ensure_capacity() {
+    let mut arena = Arena::with_capacity(16);
+    arena.allocator().push_mark();
+    arena.ensure_capacity(1 << 20);
+    arena.allocator().alloc(5u32);
+    unsafe { arena.allocator().pop_mark(); }
+    assert_eq!(arena.bytes_used(), 0);
+    assert!(arena.capacity() >= 1 << 20);
+}
+
+#[test]
+#[should_panic(expected = "no mark")]
+fn pop_mark_without_mark() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    allocator.push_mark();
+    allocator.arena.reset();
+    unsafe { allocator.pop_mark(); }
+}
+
+#[test]
+fn template_survives_ensure_capacity() {
+    let mut arena = Arena::with_capacity(16);
+    arena.freeze_template();
+    arena.ensure_capacity(1 << 20);
+    arena.allocator().alloc(5u32);
+    arena.reset();
+    assert_eq!(arena.bytes_used(), 0);
+    assert!(arena.capacity() >= 1 << 20);
+}
+
+#[test]
+fn reset_keeps_template() {
+    let mut arena = Arena::with_capacity(16);
+    let (first, second) = {
+        let mut allocator = arena.allocator();
+        (allocator.alloc_offset(11u64), allocator.alloc_offset([22u32; 4]))
+    };
+    arena.freeze_template();
+    let template_bytes = arena.bytes_used();
+
+    for frame in 0..3u64 {
+        {
+            let mut allocator = arena.allocator();
+            for i in 0..20 {
+                allocator.alloc(frame * 100 + i);
+            }
+        }
+        assert!(arena.chunk_count() > 2);
+        arena.reset();
+        assert_eq!(arena.bytes_used(), template_bytes);
+        assert_eq!(arena.chunk_count(), 2);
+        unsafe {
+            assert_eq!(*arena.resolve::<u64>(first), 11);
+            assert_eq!(*arena.resolve::<[u32; 4]>(second), [22; 4]);
+        }
+    }
+
+    arena.clear_template();
+    arena.reset();
+    assert_eq!(arena.bytes_used(), 0);
+}
+
+#[test]
+fn iterate_homogeneous_arena() {
+    let mut arena = Arena::with_capacity(20);
+    {
+        let mut allocator = arena.allocator();
+        for i in 0..10u32 {
+            allocator.alloc(i * 3);
+        }
+        allocator.alloc_slice(&[100u32, 200]);
+    }
+    assert!(arena.chunk_count() > 1);
+
+    let values: Vec<u32> = unsafe { arena.iter_as::<u32>().cloned().collect() };
+    let mut expected: Vec<u32> = (0..10).map(|i| i * 3).collect();
+    expected.extend_from_slice(&[100, 200]);
+    assert_eq!(values, expected);
+}
+
+#[test]
+fn page_aligned_chunks() {
+    let mut arena = Arena::with_page_aligned_chunks(true);
+    assert_eq!(arena.capacity(), PAGE_SIZE);
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc_slice(&[1u8; 5000]);
+        allocator.alloc_slice(&[2u8; 20000]);
+    }
+    arena.ensure_capacity(PAGE_SIZE * 6 + 1);
+    assert_eq!(arena.chunk_count(), 4);
+    let mut iter = Some(&arena.head);
+    while let Some(chunk) = iter {
+        let capacity = chunk.data.capacity();
+        assert!(capacity.is_multiple_of(PAGE_SIZE), "{} is not page aligned", capacity);
+        iter = chunk.next.as_deref();
+    }
+
+    assert_eq!(Arena::with_page_aligned_chunks(false).capacity(), 1000);
+    // Rounding up would break the budget, so it is skipped.
+    let budgeted = Arena::with_config(ArenaConfig {
+        initial_capacity: 100,
+        max_total_bytes: 1000,
+        page_aligned_chunks: true,
+        ..Default::default()
+    });
+    assert_eq!(budgeted.capacity(), 100);
+}
+
+#[test]
+fn pair_is_contiguous() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    allocator.alloc(1u8);
+
+    let (count, values) = allocator.alloc_pair(3u16, [1.5f64, 2.5, 3.5]);
+    assert_eq!((*count, *values), (3, [1.5, 2.5, 3.5]));
+    assert_eq!(values as *mut _ as usize, count as *mut _ as usize + 8);
+
+    let (tag, id, flags) = allocator.alloc_triple(b'x', 77u32, [true, false]);
+    assert_eq!((*tag, *id, *flags), (b'x', 77, [true, false]));
+    assert_eq!(id as *mut _ as usize, tag as *mut _ as usize + 4);
+    assert_eq!(flags as *mut _ as usize, id as *mut _ as usize + 4);
+}
+
+#[test]
+#[should_panic(expected = "grow past 4096 bytes")]
+fn growth_tripwire_panics() {
+    let mut arena = Arena::with_capacity(1024);
+    arena.set_panic_on_growth_over(4096);
+    let mut allocator = arena.allocator();
+    for _ in 0..100 {
+        let _ = allocator.try_alloc_slice(&[0u8; 100]);
+    }
+}
+
+#[test]
+fn growth_under_tripwire() {
+    let mut arena = Arena::with_capacity(1024);
+    arena.set_panic_on_growth_over(8192);
+    arena.allocator().alloc_slice(&[0u8; 2000]);
+    assert_eq!(arena.capacity(), 1024 + 4000);
+}
+
+#[test]
+fn copy_roots_into_another_arena() {
+    let mut old = Arena::new();
+    let (a, b) = {
+        let mut allocator = old.allocator();
+        allocator.alloc([0u8; 50]);
+        (allocator.alloc(1u64) as *const u64, allocator.alloc(2u64) as *const u64)
+    };
+    let mut new = Arena::new();
+    let remap = {
+        let roots = unsafe { [&*b, &*a, &*b] };
+        old.copy_into(&mut new.allocator(), &roots)
+    };
+    assert_eq!(remap.len(), 3);
+    assert_eq!(remap[0].0, b);
+    assert_eq!(remap[1].0, a);
+    assert_eq!(remap[0].1, remap[2].1);
+    assert!(remap[0].1 != remap[1].1);
+    drop(old);
+
+    assert_eq!(new.bytes_used(), 16);
+    unsafe {
+        assert_eq!(*remap[0].1, 2);
+        assert_eq!(*remap[1].1, 1);
+    }
+    assert!(new.contains(remap[1].1));
+}
+
+#[test]
+fn tagged_allocations_name_their_arena() {
+    let mut first = Arena::new();
+    let mut second = Arena::new();
+    let (first_id, x) = first.allocator().alloc_tagged(1u32);
+    assert_eq!(*x, 1);
+    let (second_id, _) = second.allocator().alloc_tagged(2u32);
+    assert!(first_id != second_id);
+    assert!(first.owns_id(first_id));
+    assert!(!first.owns_id(second_id));
+    assert!(second.owns_id(second_id));
+    assert_eq!(first.allocator().alloc_tagged(3u8).0, first_id);
+    assert!(!first.deep_clone().owns_id(first_id));
+}
+
+#[test]
+fn slice_from_array_literal() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let xs = allocator.alloc_from_array([1, 2, 3]);
+    xs[0] = 10;
+    assert_eq!(xs, &[10, 2, 3]);
+    let none: &mut [u64] = allocator.alloc_from_array([]);
+    assert!(none.is_empty());
+}
+
+#[test]
+fn allocations_before_growth() {
+    let mut arena = Arena::with_capacity(40);
+    for i in 0..10u32 {
+        arena.allocator().alloc(i);
+    }
+    assert_eq!(arena.allocations_before_first_growth(), None);
+    arena.allocator().alloc(10u32);
+    arena.allocator().alloc([0u8; 100]);
+    assert_eq!(arena.allocations_before_first_growth(), Some(10));
+    arena.reset();
+    assert_eq!(arena.allocations_before_first_growth(), Some(10));
+}
+
+#[test]
+fn eager_and_lazy_first_chunk() {
+    let eager = Arena::with_config(ArenaConfig { initial_capacity: 256, ..Default::default() });
+    assert_eq!(eager.capacity(), 256);
+
+    let mut lazy = Arena::with_config(ArenaConfig {
+        initial_capacity: 256,
+        eager: false,
+        ..Default::default()
+    });
+    assert_eq!(lazy.capacity(), 0);
+    assert_eq!(Arena::try_with_config(lazy.config()).unwrap().capacity(), 0);
+    assert_eq!(*lazy.allocator().alloc(7u32), 7);
+    assert_eq!(lazy.capacity(), 256);
+    assert_eq!(lazy.chunk_count(), 1);
+    assert!(!lazy.has_grown());
+
+    let mut big_first = Arena::with_config(ArenaConfig {
+        initial_capacity: 16,
+        eager: false,
+        ..Default::default()
+    });
+    big_first.allocator().alloc([0u8; 100]);
+    assert!(big_first.capacity() >= 100);
+    assert_eq!(big_first.chunk_count(), 1);
+}
+
+#[test]
+fn empty_until_allocated() {
+    let mut arena = Arena::new();
+    assert!(arena.is_empty());
+    arena.allocator().alloc(());
+    assert!(arena.is_empty());
+    arena.allocator().alloc(1u8);
+    assert!(!arena.is_empty());
+    arena.reset();
+    assert!(arena.is_empty());
+}
+
+#[test]
+fn allocate_in_chosen_chunk() {
+    // Allocation order is oldest chunk first, so the chunk behind the head
+    // is chunk 0.
+    let mut arena = Arena::with_chunks(&[16, 32]);
+    {
+        let mut allocator = arena.allocator();
+        assert_eq!(*allocator.alloc_in_chunk(1, 1u32).unwrap(), 1);
+        assert_eq!(*allocator.alloc_in_chunk(0, 2u32).unwrap(), 2);
+        assert_eq!(*allocator.alloc_in_chunk(0, 3u32).unwrap(), 3);
+        assert!(allocator.alloc_in_chunk(1, [0u8; 13]).is_none());
+        assert!(allocator.alloc_in_chunk(2, 0u8).is_none());
+    }
+    assert_eq!(arena.chunk_count(), 2);
+    assert_eq!(arena.bytes_used(), 12);
+    assert_eq!(arena.validate(), Ok(()));
+    let sizes: Vec<usize> = arena.chunks().map(|chunk| chunk.len()).collect();
+    assert_eq!(sizes, [8, 4]);
+}
+
+#[test]
+fn chosen_chunk_is_rewound_by_marks() {
+    let mut arena = Arena::with_chunks(&[16, 32]);
+    let mut allocator = arena.allocator();
+    allocator.alloc(1u32);
+    allocator.push_mark();
+    assert!(allocator.alloc_in_chunk(0, 2u32).is_none());
+    assert_eq!(*allocator.alloc_in_chunk(1, 3u32).unwrap(), 3);
+    unsafe { allocator.pop_mark(); }
+    assert_eq!(arena.bytes_used(), 4);
+
+    arena.freeze_template();
+    assert!(arena.allocator().alloc_in_chunk(0, 4u32).is_none());
+    arena.reset();
+    assert_eq!(arena.bytes_used(), 4);
+}
+
+#[test]
+fn chosen_chunk_respects_single_allocation_limit() {
+    let mut arena = Arena::with_config(ArenaConfig {
+        initial_capacity: 64,
+        max_single_allocation: 8,
+        ..Default::default()
+    });
+    let mut allocator = arena.allocator();
+    assert!(allocator.alloc_in_chunk(0, [0u8; 9]).is_none());
+    assert_eq!(*allocator.alloc_in_chunk(0, [1u8; 8]).unwrap(), [1; 8]);
+    assert_eq!(arena.bytes_used(), 8);
+}
+
+#[cfg(not(feature = "abort-on-overflow"))]
+#[test]
+fn overflow_panic_names_sizes() {
+    use std::panic;
+
+    let result = panic::catch_unwind(|| {
+        let mut arena = Arena::new();
+        arena.allocator().alloc_slice_default::<u64>(usize::MAX / 4);
+    });
+    let payload = result.unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert_eq!(*message, format!("Arena slice allocation overflow: len={} element_size=8", usize::MAX / 4));
+}
+
+#[test]
+fn iterate_allocated_copies() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let data = [3u16, 1, 4, 1, 5];
+    let values = allocator.alloc_into_iter(&data);
+    assert_eq!(values.len(), 5);
+    assert_eq!(values.collect::<Vec<u16>>(), data);
+    assert_eq!(allocator.alloc_into_iter(&data).next_back(), Some(5));
+}
+
+#[test]
+fn fill_ratios_show_trapped_space() {
+    let mut arena = Arena::with_capacity(100);
+    assert_eq!(arena.chunk_fill_ratios(), [0.0]);
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc([0u8; 60]);
+        allocator.alloc([0u8; 50]);
+    }
+    let ratios = arena.chunk_fill_ratios();
+    assert_eq!(ratios.len(), 2);
+    assert_eq!(ratios[0], 0.6);
+    assert!(ratios[1] > 0.0 && ratios[1] < 1.0);
+}
+
+#[test]
+fn isolated_allocation_starts_a_chunk() {
+    let mut arena = Arena::with_capacity(64);
+    let mut allocator = arena.allocator();
+    allocator.alloc(1u8);
+
+    let value = allocator.alloc_isolated([9u64; 4]);
+    assert_eq!(*value, [9; 4]);
+    assert_eq!(allocator.arena.chunk_count(), 2);
+    let base = allocator.arena.head.data.as_ptr() as usize;
+    assert_eq!(value.as_ptr() as usize, base + padding_for(base, 8));
+    assert!(allocator.arena.head.data.capacity() < 64);
+
+    allocator.alloc([2u8; 8]);
+    assert_eq!(allocator.arena.chunk_count(), 3);
+}
+
+#[test]
+fn reset_scoped_and_arena_scoped_drops() {
+    use std::sync::Arc;
+
+    let per_frame = Arc::new(());
+    let whole_life = Arc::new(());
+    let mut arena = Arena::new();
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc_reset_dropped(per_frame.clone());
+        let kept = allocator.alloc_arena_dropped(whole_life.clone());
+        assert_eq!(Arc::strong_count(kept), 2);
+    }
+
+    arena.reset();
+    assert_eq!(Arc::strong_count(&per_frame), 1);
+    assert_eq!(Arc::strong_count(&whole_life), 2);
+    // The kept value's old memory is free for reuse.
+    arena.allocator().alloc([0xFFu8; 64]);
+    assert_eq!(arena.validate(), Ok(()));
+
+    arena.allocator().alloc_arena_dropped(whole_life.clone());
+    assert_eq!(Arc::strong_count(&whole_life), 3);
+    drop(arena);
+    assert_eq!(Arc::strong_count(&whole_life), 1);
+}
+
+#[test]
+fn pop_mark_splits_drop_lists() {
+    use std::sync::Arc;
+
+    let per_frame = Arc::new(());
+    let whole_life = Arc::new(());
+    let mut arena = Arena::new();
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc_reset_dropped(per_frame.clone());
+        allocator.push_mark();
+        allocator.alloc_arena_dropped(whole_life.clone());
+        allocator.alloc_reset_dropped(per_frame.clone());
+        unsafe { allocator.pop_mark(); }
+    }
+    assert_eq!(Arc::strong_count(&per_frame), 2);
+    assert_eq!(Arc::strong_count(&whole_life), 2);
+    assert_eq!(arena.registered_drop_count(), 2);
+
+    drop(arena);
+    assert_eq!(Arc::strong_count(&per_frame), 1);
+    assert_eq!(Arc::strong_count(&whole_life), 1);
+}
+
+#[test]
+fn query_usage_through_allocator() {
+    let mut arena = Arena::with_capacity(32);
+    let mut allocator = arena.allocator();
+    allocator.alloc(1u32);
+    allocator.alloc_slice(&[0u8; 10]);
+    assert_eq!(allocator.bytes_used(), 14);
+    assert_eq!(allocator.capacity(), 32);
+    assert_eq!(allocator.chunk_count(), 1);
+
+    allocator.alloc([0u8; 40]);
+    assert_eq!(allocator.chunk_count(), 2);
+    assert_eq!(allocator.bytes_used(), 54);
+}
+
+#[test]
+fn terminated_slice() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    let terminated = allocator.alloc_slice_terminated(&[5i32, 6, 7], -1);
+    assert_eq!(terminated, &[5, 6, 7, -1]);
+    assert_eq!(allocator.alloc_slice_terminated::<i32>(&[], -1), &[-1]);
+}
+
+#[test]
+fn count_registered_drops() {
+    let mut arena = Arena::new();
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc_reset_dropped(String::from("outer"));
+        allocator.push_mark();
+        allocator.alloc_reset_dropped(String::from("a"));
+        allocator.alloc_reset_dropped(vec![1u8]);
+        allocator.alloc(5u32);
+        assert_eq!(allocator.arena.registered_drop_count(), 3);
+        unsafe { allocator.pop_mark(); }
+    }
+    assert_eq!(arena.registered_drop_count(), 1);
+
+    arena.allocator().alloc_arena_dropped(String::from("kept"));
+    arena.reset();
+    assert_eq!(arena.registered_drop_count(), 1);
+}
+
+#[test]
+fn tiled_slice() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    assert_eq!(allocator.alloc_slice_tiled(&[1u8, 2, 3], 7), &[1, 2, 3, 1, 2, 3, 1]);
+    assert_eq!(allocator.alloc_slice_tiled(&[9u16, 8], 4), &[9, 8, 9, 8]);
+    assert!(allocator.alloc_slice_tiled::<u32>(&[], 0).is_empty());
+}
+
+#[test]
+fn read_only_scope_reads() {
+    let mut arena = Arena::new();
+    let offset = arena.allocator().alloc_offset(42u32);
+    let (value, used) = arena.read_only_scope(|arena| {
+        (unsafe { *arena.resolve::<u32>(offset) }, arena.bytes_used())
+    });
+    assert_eq!(value, 42);
+    assert_eq!(used, 4);
+}
+
+#[test]
+fn enumerated_slice() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    allocator.alloc(1u8);
+    let pairs = allocator.alloc_slice_enumerated(&['a', 'b', 'c']);
+    assert_eq!(pairs, &[(0, 'a'), (1, 'b'), (2, 'c')]);
+    assert_eq!(pairs.as_ptr() as usize % mem::align_of::<(usize, char)>(), 0);
+    assert!(allocator.alloc_slice_enumerated::<u64>(&[]).is_empty());
+}
+
+#[test]
+fn flat_bytes_round_trip() {
+    let mut arena = Arena::with_capacity(16);
+    let offsets: Vec<RelOffset> = {
+        let mut allocator = arena.allocator();
+        (0..5u64).map(|i| allocator.alloc_offset(i * 11)).collect()
+    };
+    assert!(arena.chunk_count() > 1);
+
+    let bytes = arena.to_flat_bytes();
+    assert_eq!(bytes.len(), arena.bytes_used());
+    assert_eq!(bytes, arena.chunks().flat_map(|chunk| chunk.iter().cloned()).collect::<Vec<u8>>());
+
+    let copy = Arena::from_flat_bytes(bytes.clone());
+    assert_eq!(copy.to_flat_bytes(), bytes);
+    for (i, &offset) in offsets.iter().enumerate() {
+        unsafe {
+            assert_eq!(*copy.resolve::<u64>(offset), *arena.resolve::<u64>(offset));
+            assert_eq!(*copy.resolve::<u64>(offset), i as u64 * 11);
+        }
+    }
+}
+
+#[test]
+fn reversed_slice() {
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+    assert_eq!(allocator.alloc_slice_reversed(&[1, 2, 3]), &[3, 2, 1]);
+    assert_eq!(allocator.alloc_slice_reversed(&[7u64]), &[7]);
+    assert!(allocator.alloc_slice_reversed::<u8>(&[]).is_empty());
+}
+
+#[test]
+fn realloc_growth_keeps_contents_contiguous() {
+    let mut arena = Arena::with_config(ArenaConfig {
+        initial_capacity: 256,
+        grow_mode: GrowMode::Realloc,
+        ..Default::default()
+    });
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc_slice(b"abc");
+        allocator.alloc_slice_simd(&[7u8; 5], 64);
+        allocator.alloc_reset_dropped(String::from("moved"));
+    }
+    let before: Vec<u8> = arena.chunks().flat_map(|chunk| chunk.iter().cloned()).collect();
+
+    arena.ensure_capacity(1000);
+    assert_eq!(arena.chunk_count(), 1);
+    assert!(arena.head.data.capacity() - arena.head.data.len() >= 1000);
+    assert_eq!(arena.validate(), Ok(()));
+
+    let chunk = arena.chunks().next().unwrap();
+    let lead = chunk.len() - before.len();
+    assert_eq!(&chunk[lead..], &before[..]);
+    let simd = chunk.iter().position(|&byte| byte == 7).unwrap();
+    assert_eq!(padding_for(chunk.as_ptr() as usize + simd, 64), 0);
+
+    // The String's destructor was moved along with it.
+    arena.reset();
+    assert_eq!(arena.registered_drop_count(), 0);
 }
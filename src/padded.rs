@@ -0,0 +1,64 @@
+//! Slices whose elements are spread out at a fixed stride.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A slice whose elements each start `stride` bytes after the previous
+/// one, with padding in between. Returned by
+/// `Allocator::alloc_slice_padded()`.
+///
+/// With a stride that divides the cache line size, no element straddles
+/// a cache line.
+pub struct PaddedSlice<'a, T: 'a> {
+    base: *mut u8,
+    len: usize,
+    stride: usize,
+    elems: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: Copy> PaddedSlice<'a, T> {
+    // `base` must point to `len` initialized `T`s, `stride` bytes apart.
+    pub(crate) unsafe fn new(base: *mut u8, len: usize, stride: usize) -> PaddedSlice<'a, T> {
+        PaddedSlice { base, len, stride, elems: PhantomData }
+    }
+
+    /// Borrow the element at `index`, or `None` if it is out of bounds.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len {
+            Some(unsafe { &*(self.base.add(index * self.stride) as *const T) })
+        } else {
+            None
+        }
+    }
+
+    /// Mutably borrow the element at `index`, or `None` if it is out of
+    /// bounds.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index < self.len {
+            Some(unsafe { &mut *(self.base.add(index * self.stride) as *mut T) })
+        } else {
+            None
+        }
+    }
+
+    /// Get the number of elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the distance in bytes from each element to the next.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+}
+
+impl<'a, T: Copy + fmt::Debug> fmt::Debug for PaddedSlice<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries((0..self.len).map(|index| self.get(index).unwrap())).finish()
+    }
+}
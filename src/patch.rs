@@ -0,0 +1,46 @@
+//! Space set aside in an arena, to be written once its value is known.
+
+use std::fmt;
+use std::mem::MaybeUninit;
+
+/// A reserved, not yet written slot for a `T`. Returned by
+/// `Allocator::reserve_patch()`.
+///
+/// Like a `FillCursor`, it doesn't borrow the allocator, so the allocator
+/// can go on allocating what comes after the slot before it is filled.
+pub struct Patch<'a, T: 'a> {
+    slot: &'a mut MaybeUninit<T>,
+}
+
+impl<'a, T: Copy> Patch<'a, T> {
+    pub(crate) fn new(slot: &'a mut MaybeUninit<T>) -> Patch<'a, T> {
+        Patch { slot }
+    }
+
+    /// Write the reserved slot, getting a reference to the written value.
+    pub fn set(self, value: T) -> &'a mut T {
+        self.slot.write(value)
+    }
+}
+
+impl<'a, T> fmt::Debug for Patch<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("Patch {{ address: {:p} }}", self.slot))
+    }
+}
+
+
+#[test]
+fn patch_length_prefix() {
+    let mut arena = super::Arena::new();
+    let mut allocator = arena.allocator();
+
+    let length = allocator.reserve_patch::<u32>();
+    let body = allocator.alloc_slice(b"payload of some length");
+    let length = length.set(body.len() as u32);
+    assert_eq!(*length, 22);
+
+    let contents: Vec<u8> = allocator.arena.chunks().flat_map(|chunk| chunk.iter().cloned()).collect();
+    assert_eq!(contents[..4], 22u32.to_ne_bytes());
+    assert_eq!(&contents[4..], b"payload of some length");
+}
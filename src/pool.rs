@@ -0,0 +1,156 @@
+//! Recycled slots for values of a single type.
+
+use std::fmt;
+use std::mem;
+use std::ptr::NonNull;
+
+use super::{Allocator, Arena};
+
+// A slot holds either a live value or, once freed, a link to the next free
+// slot, so the free list costs no memory of its own.
+#[derive(Copy, Clone)]
+#[repr(C)]
+union Slot<T: Copy> {
+    value: T,
+    next: Option<NonNull<Slot<T>>>,
+}
+
+/// Allocates `T`s through an arena, but takes freed ones back and hands
+/// their slots out again, so allocating and freeing in a loop doesn't keep
+/// growing the arena.
+///
+/// Freed slots are kept in a list threaded through the slots themselves.
+/// Each slot is big enough for either a `T` or a pointer.
+///
+/// The pool borrows its arena for as long as its values live, so nothing
+/// but a pool slot can be handed back to `free()`:
+///
+/// ```compile_fail
+/// use copy_arena::{Arena, PoolArena};
+///
+/// let mut arena = Arena::new();
+/// let mut allocator = arena.allocator();
+/// let foreign = allocator.alloc(1u8);
+/// let mut pool = PoolArena::new(&mut arena);
+/// pool.free(foreign);
+/// ```
+pub struct PoolArena<'a, T: Copy + 'a> {
+    allocator: Allocator<'a>,
+    free: Option<NonNull<Slot<T>>>,
+    free_count: usize,
+}
+
+impl<'a, T: Copy + 'a> PoolArena<'a, T> {
+    /// Construct a PoolArena that allocates new slots in `arena`.
+    pub fn new(arena: &'a mut Arena) -> PoolArena<'a, T> {
+        PoolArena { allocator: arena.allocator(), free: None, free_count: 0 }
+    }
+
+    /// Allocate a copy of `elem`, reusing the most recently freed slot if
+    /// there is one.
+    pub fn alloc(&mut self, elem: T) -> &'a mut T {
+        match self.free {
+            Some(slot) => unsafe {
+                let slot = &mut *slot.as_ptr();
+                self.free = slot.next;
+                self.free_count -= 1;
+                *slot = Slot { value: elem };
+                &mut slot.value
+            },
+            None => {
+                let slot: &'a mut Slot<T> = self.allocator.alloc(Slot { value: elem });
+                unsafe { &mut slot.value }
+            }
+        }
+    }
+
+    /// Give back a value allocated by this pool, so its slot can be reused.
+    ///
+    /// Taking the reference by value ends the caller's access to it. Only
+    /// the pool can allocate in its arena while it is borrowed, so a value
+    /// from the arena came from this pool's `alloc()`. Panics if it didn't
+    /// come from the pool's arena, or isn't laid out like one of its
+    /// slots, since its memory is about to be overwritten with a free-list
+    /// link.
+    pub fn free(&mut self, value: &'a mut T) {
+        let slot = value as *mut T as *mut Slot<T>;
+        let last_byte = (slot as usize + mem::size_of::<Slot<T>>() - 1) as *const u8;
+        assert!((slot as usize).is_multiple_of(mem::align_of::<Slot<T>>())
+                    && self.allocator.arena.contains(slot as *const Slot<T>)
+                    && self.allocator.arena.contains(last_byte),
+                "PoolArena freed a value from outside its arena");
+        unsafe { (*slot).next = self.free; }
+        self.free = NonNull::new(slot);
+        self.free_count += 1;
+    }
+
+    /// Get the number of freed slots waiting to be reused.
+    pub fn free_count(&self) -> usize {
+        self.free_count
+    }
+}
+
+impl<'a, T: Copy + 'a> fmt::Debug for PoolArena<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("PoolArena {{ free_count: {} }}", self.free_count))
+    }
+}
+
+
+#[test]
+fn pool_reuses_freed_slots() {
+    let mut arena = super::Arena::new();
+    let mut pool = PoolArena::new(&mut arena);
+
+    let a = pool.alloc(1u8);
+    let b = pool.alloc(2u8);
+    let a_addr = a as *mut u8 as usize;
+    pool.free(a);
+    assert_eq!(pool.free_count(), 1);
+
+    let c = pool.alloc(3u8);
+    assert_eq!(c as *mut u8 as usize, a_addr);
+    assert_eq!((*b, *c), (2, 3));
+    assert_eq!(pool.free_count(), 0);
+}
+
+#[test]
+fn pool_growth_is_bounded() {
+    #[derive(Copy, Clone)]
+    struct Particle {
+        _position: [f32; 3],
+        _life: u16,
+    }
+
+    let mut arena = super::Arena::new();
+    let used_after_first_frame = {
+        let mut pool = PoolArena::new(&mut arena);
+        let mut live = Vec::new();
+        let mut used = 0;
+        for frame in 0..50 {
+            for _ in 0..20 {
+                live.push(pool.alloc(Particle { _position: [0.0; 3], _life: frame }));
+            }
+            for particle in live.drain(..) {
+                pool.free(particle);
+            }
+            assert_eq!(pool.free_count(), 20);
+            if frame == 0 {
+                used = pool.allocator.arena.bytes_used();
+            }
+            assert_eq!(pool.allocator.arena.bytes_used(), used);
+        }
+        used
+    };
+    assert_eq!(arena.bytes_used(), used_after_first_frame);
+}
+
+#[test]
+#[should_panic(expected = "from outside its arena")]
+fn pool_rejects_foreign_values() {
+    let mut arena = super::Arena::new();
+    let mut pool = PoolArena::new(&mut arena);
+    pool.alloc(1u64);
+    let mut foreign = Box::new(2u64);
+    pool.free(&mut foreign);
+}
@@ -0,0 +1,73 @@
+//! Records built field by field on top of default values.
+
+use std::fmt;
+
+/// A slice of default values being filled in, which remembers which
+/// elements have been set. Returned by `Allocator::alloc_record()`.
+pub struct RecordBuilder<'a, T: 'a> {
+    fields: &'a mut [T],
+    set: Vec<bool>,
+}
+
+impl<'a, T: Copy> RecordBuilder<'a, T> {
+    pub(crate) fn new(fields: &'a mut [T]) -> RecordBuilder<'a, T> {
+        let set = vec![false; fields.len()];
+        RecordBuilder { fields, set }
+    }
+
+    /// Set the element at `index`. Panics if it is out of bounds.
+    pub fn set(&mut self, index: usize, value: T) -> &mut RecordBuilder<'a, T> {
+        self.fields[index] = value;
+        self.set[index] = true;
+        self
+    }
+
+    /// Whether the element at `index` has been set.
+    pub fn is_set(&self, index: usize) -> bool {
+        self.set.get(index).cloned().unwrap_or(false)
+    }
+
+    /// Get the record, with every element that wasn't set left at its
+    /// default.
+    pub fn finish(self) -> &'a mut [T] {
+        self.fields
+    }
+
+    /// Get the record, provided every element in `required` was set.
+    /// Otherwise, fails with the first required index that wasn't.
+    pub fn finish_required(self, required: &[usize]) -> Result<&'a mut [T], usize> {
+        match required.iter().find(|&&index| !self.is_set(index)) {
+            Some(&missing) => Err(missing),
+            None => Ok(self.fields),
+        }
+    }
+}
+
+impl<'a, T> fmt::Debug for RecordBuilder<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let set = self.set.iter().filter(|&&set| set).count();
+        f.write_fmt(format_args!("RecordBuilder {{ len: {}, set: {} }}", self.fields.len(), set))
+    }
+}
+
+
+#[test]
+fn record_keeps_defaults() {
+    use super::Arena;
+
+    let mut arena = Arena::new();
+    let mut allocator = arena.allocator();
+
+    let mut record = allocator.alloc_record::<u32>(5);
+    record.set(1, 10).set(3, 30);
+    assert!(record.is_set(3) && !record.is_set(4));
+    assert_eq!(record.finish_required(&[1, 3]).unwrap(), &[0, 10, 0, 30, 0]);
+
+    let mut record = allocator.alloc_record::<u32>(3);
+    record.set(0, 1);
+    assert_eq!(record.finish_required(&[0, 2, 1]).unwrap_err(), 2);
+
+    let mut record = allocator.alloc_record::<i8>(2);
+    record.set(1, -1);
+    assert_eq!(record.finish(), &[0, -1]);
+}
@@ -0,0 +1,70 @@
+//! A buffer allocated once and reused in place.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// An aligned buffer in an arena, to be cleared and reused on each pass of
+/// a loop instead of allocating a fresh one every time. Returned by
+/// `Allocator::scratch()`.
+///
+/// The buffer's region belongs to it for as long as the arena's allocations
+/// live: reusing it never grows the arena, but the space isn't given back
+/// until the arena is reset or dropped.
+pub struct ScratchBuffer<'a, T: 'a> {
+    data: &'a mut [T],
+}
+
+impl<'a, T: Copy + Default> ScratchBuffer<'a, T> {
+    pub(crate) fn new(data: &'a mut [T]) -> ScratchBuffer<'a, T> {
+        ScratchBuffer { data }
+    }
+
+    /// Set every element back to its default (zero, for numbers), ready for
+    /// the next pass.
+    pub fn reset_contents(&mut self) {
+        for elem in self.data.iter_mut() {
+            *elem = Default::default();
+        }
+    }
+}
+
+impl<'a, T> Deref for ScratchBuffer<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.data
+    }
+}
+
+impl<'a, T> DerefMut for ScratchBuffer<'a, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.data
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for ScratchBuffer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+
+#[test]
+fn reuse_across_iterations() {
+    let mut arena = super::Arena::with_capacity(1024);
+    let mut allocator = arena.allocator();
+    let mut scratch = allocator.scratch::<u32>(16, 64);
+    assert_eq!(scratch.as_ptr() as usize % 64, 0);
+    let used = allocator.bytes_used();
+
+    for pass in 1..10u32 {
+        scratch.reset_contents();
+        assert!(scratch.iter().all(|&x| x == 0));
+        for (i, x) in scratch.iter_mut().enumerate() {
+            *x = pass * i as u32;
+        }
+        assert_eq!(scratch[15], pass * 15);
+    }
+    assert_eq!(allocator.bytes_used(), used);
+    assert_eq!(allocator.chunk_count(), 1);
+}
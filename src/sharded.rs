@@ -0,0 +1,80 @@
+//! Arenas split into shards that threads fill independently.
+
+use std::fmt;
+
+use super::Arena;
+
+/// A set of independent arenas, one per thread, merged into a single arena
+/// once the threads are done.
+///
+/// Each shard is an ordinary `Arena`, so threads filling different shards
+/// never contend.
+pub struct ShardedArena {
+    shards: Vec<Arena>,
+}
+
+impl ShardedArena {
+    /// Construct a ShardedArena with `num_shards` empty shards. Panics if
+    /// `num_shards` is zero.
+    pub fn new(num_shards: usize) -> ShardedArena {
+        assert!(num_shards > 0, "ShardedArena needs at least one shard");
+        ShardedArena { shards: (0..num_shards).map(|_| Arena::new()).collect() }
+    }
+
+    /// Get shard `index`.
+    pub fn shard(&mut self, index: usize) -> &mut Arena {
+        &mut self.shards[index]
+    }
+
+    /// Get every shard at once, e.g. to hand one to each thread.
+    pub fn shards_mut(&mut self) -> &mut [Arena] {
+        &mut self.shards
+    }
+
+    /// Merge all the shards into one arena, as by `Arena::absorb`.
+    pub fn merge_all(self) -> Arena {
+        let mut shards = self.shards.into_iter();
+        let mut merged = shards.next().expect("ShardedArena always has a shard");
+        for shard in shards {
+            merged.absorb(shard);
+        }
+        merged
+    }
+}
+
+impl fmt::Debug for ShardedArena {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("ShardedArena {{ shards: {} }}", self.shards.len()))
+    }
+}
+
+
+#[test]
+fn merge_shards_from_threads() {
+    use std::thread;
+
+    let mut sharded = ShardedArena::new(2);
+    thread::scope(|scope| {
+        for (index, shard) in sharded.shards_mut().iter_mut().enumerate() {
+            scope.spawn(move || {
+                let mut allocator = shard.allocator();
+                // As byte arrays, so no chunk gets alignment padding.
+                for value in 0..500u32 {
+                    allocator.alloc((value * 2 + index as u32).to_ne_bytes());
+                }
+            });
+        }
+    });
+    assert_eq!(sharded.shard(1).allocation_count(), 500);
+
+    let merged = sharded.merge_all();
+    assert_eq!(merged.allocation_count(), 1000);
+    assert_eq!(merged.validate(), Ok(()));
+    let bytes: Vec<u8> = merged.chunks().flat_map(|chunk| chunk.iter().cloned()).collect();
+    assert_eq!(bytes.len(), 4000);
+    let mut values: Vec<u32> = bytes.chunks_exact(4)
+        .map(|word| u32::from_ne_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    values.sort_unstable();
+    assert_eq!(values, (0..1000).collect::<Vec<u32>>());
+}
@@ -0,0 +1,125 @@
+//! A pool of recyclable slots over a `TypedArena`.
+
+use std::fmt;
+
+use super::TypedArena;
+
+/// Identifies a slot in a `SlotArena`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SlotId(usize);
+
+/// An arena of `T`s whose slots can be freed and then reused by later
+/// allocations, for things like entities that come and go.
+///
+/// Slots never move, and freeing one doesn't give memory back to the
+/// arena; it only lets the next `alloc` overwrite it. A freed slot's id
+/// becomes valid again once the slot is reused, so holding on to ids of
+/// freed slots is a logic error.
+pub struct SlotArena<T> {
+    slots: TypedArena<T>,
+    // Whether each slot holds a live value.
+    occupied: Vec<bool>,
+    // Freed slots, most recently freed last.
+    free: Vec<usize>,
+}
+
+impl<T: Copy> SlotArena<T> {
+    /// Construct a new, empty SlotArena.
+    pub fn new() -> SlotArena<T> {
+        SlotArena {
+            slots: TypedArena::new(),
+            occupied: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Store `elem` in a slot, reusing the most recently freed one if
+    /// there is one.
+    pub fn alloc(&mut self, elem: T) -> SlotId {
+        match self.free.pop() {
+            Some(index) => {
+                self.slots[index] = elem;
+                self.occupied[index] = true;
+                SlotId(index)
+            }
+            None => {
+                self.slots.alloc(elem);
+                self.occupied.push(true);
+                SlotId(self.occupied.len() - 1)
+            }
+        }
+    }
+
+    /// Free the slot `id`, so a later `alloc` can reuse it. Returns false
+    /// if it was already free.
+    pub fn free(&mut self, id: SlotId) -> bool {
+        if !self.is_occupied(id) {
+            return false;
+        }
+        self.occupied[id.0] = false;
+        self.free.push(id.0);
+        true
+    }
+
+    /// Borrow the value in slot `id`, if it hasn't been freed.
+    pub fn get(&self, id: SlotId) -> Option<&T> {
+        if self.is_occupied(id) { Some(&self.slots[id.0]) } else { None }
+    }
+
+    /// Mutably borrow the value in slot `id`, if it hasn't been freed.
+    pub fn get_mut(&mut self, id: SlotId) -> Option<&mut T> {
+        if self.is_occupied(id) { Some(&mut self.slots[id.0]) } else { None }
+    }
+
+    /// Get the number of occupied slots.
+    pub fn len(&self) -> usize {
+        self.occupied.len() - self.free.len()
+    }
+
+    /// Whether no slots are occupied.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn is_occupied(&self, id: SlotId) -> bool {
+        self.occupied.get(id.0).cloned().unwrap_or(false)
+    }
+}
+
+impl<T: Copy> Default for SlotArena<T> {
+    fn default() -> SlotArena<T> {
+        SlotArena::new()
+    }
+}
+
+impl<T> fmt::Debug for SlotArena<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("SlotArena {{ slots: {}, free: {} }}", self.occupied.len(), self.free.len()))
+    }
+}
+
+
+#[test]
+fn slots_are_recycled() {
+    let mut arena = SlotArena::new();
+    let a = arena.alloc(1u32);
+    let b = arena.alloc(2u32);
+    let c = arena.alloc(3u32);
+    assert_eq!(arena.len(), 3);
+
+    assert!(arena.free(b));
+    assert!(!arena.free(b));
+    assert_eq!(arena.get(b), None);
+    assert_eq!(arena.len(), 2);
+
+    let d = arena.alloc(4u32);
+    assert_eq!(d, b);
+    assert_eq!(arena.get(d), Some(&4));
+
+    *arena.get_mut(a).unwrap() += 10;
+    assert_eq!((arena.get(a), arena.get(c)), (Some(&11), Some(&3)));
+
+    let e = arena.alloc(5u32);
+    assert!(e != a && e != c && e != d);
+    assert_eq!(arena.len(), 4);
+}
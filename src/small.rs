@@ -0,0 +1,73 @@
+//! Slices kept inline when small, and in an arena otherwise.
+
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+use std::slice;
+
+/// A slice of up to `N` elements stored inline, or a longer one stored in
+/// an arena. Returned by `Allocator::alloc_slice_or_inline()`.
+///
+/// Either way it derefs to `[T]`.
+pub struct SmallArenaSlice<'a, T: 'a, const N: usize> {
+    repr: Repr<'a, T, N>,
+}
+
+enum Repr<'a, T: 'a, const N: usize> {
+    // Only the first `len` elements of `buf` are initialized.
+    Inline { buf: [MaybeUninit<T>; N], len: usize },
+    Spilled(&'a mut [T]),
+}
+
+impl<'a, T: Copy, const N: usize> SmallArenaSlice<'a, T, N> {
+    pub(crate) fn inline(elems: &[T]) -> SmallArenaSlice<'a, T, N> {
+        assert!(elems.len() <= N);
+        let mut buf = [MaybeUninit::uninit(); N];
+        for (dest, src) in buf.iter_mut().zip(elems.iter()) {
+            *dest = MaybeUninit::new(*src);
+        }
+        SmallArenaSlice { repr: Repr::Inline { buf, len: elems.len() } }
+    }
+
+    pub(crate) fn spilled(slice: &'a mut [T]) -> SmallArenaSlice<'a, T, N> {
+        SmallArenaSlice { repr: Repr::Spilled(slice) }
+    }
+
+    /// Whether the elements are stored inline rather than in the arena.
+    pub fn is_inline(&self) -> bool {
+        match self.repr {
+            Repr::Inline { .. } => true,
+            Repr::Spilled(_) => false,
+        }
+    }
+}
+
+impl<'a, T: Copy, const N: usize> Deref for SmallArenaSlice<'a, T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self.repr {
+            Repr::Inline { ref buf, len } => unsafe {
+                slice::from_raw_parts(buf.as_ptr() as *const T, len)
+            },
+            Repr::Spilled(ref slice) => slice,
+        }
+    }
+}
+
+impl<'a, T: Copy, const N: usize> DerefMut for SmallArenaSlice<'a, T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match self.repr {
+            Repr::Inline { ref mut buf, len } => unsafe {
+                slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut T, len)
+            },
+            Repr::Spilled(ref mut slice) => slice,
+        }
+    }
+}
+
+impl<'a, T: Copy + fmt::Debug, const N: usize> fmt::Debug for SmallArenaSlice<'a, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,158 @@
+//! Records of an arena's usage at a point in time.
+
+/// An arena's usage at one point in time, returned by `Arena::snapshot()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Bytes allocated, as `Arena::bytes_used()` reports.
+    pub bytes_used: usize,
+    /// Chunks held, as `Arena::chunk_count()` reports.
+    pub chunk_count: usize,
+    /// Allocations made, as `Arena::allocation_count()` reports.
+    pub allocation_count: usize,
+}
+
+/// How an arena's usage changed between two snapshots. Each field is
+/// negative if it went down, e.g. across a reset.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UsageDelta {
+    /// The change in bytes allocated.
+    pub bytes_used: isize,
+    /// The change in chunks held.
+    pub chunk_count: isize,
+    /// The change in allocations made.
+    pub allocation_count: isize,
+}
+
+/// A suggestion for configuring an arena like this one, returned by
+/// `Arena::tuning_hint()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TuningHint {
+    /// A `with_capacity` value that would have held the arena's peak usage
+    /// in its first chunk: the peak rounded up to a power of two.
+    pub suggested_capacity: usize,
+    /// Whether the arena needed more than one chunk at its peak, so growing
+    /// by doubling cost extra chunks and left space trapped in them. If
+    /// so, reserving `suggested_capacity` up front avoids that.
+    pub reserve_up_front: bool,
+    /// The most chunks the arena held at once.
+    pub peak_chunk_count: usize,
+    /// Bytes lost to alignment padding, as `Arena::padding_bytes()` reports.
+    /// A large share of the peak suggests reordering allocations by
+    /// alignment.
+    pub padding_bytes: usize,
+}
+
+/// All of an arena's statistics at one point in time, returned by
+/// `Arena::metrics()`. With the `serde` feature, it can be serialized.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+pub struct ArenaMetrics {
+    /// Bytes reserved by the chunks, as `Arena::capacity()` reports.
+    pub capacity: usize,
+    /// Bytes allocated, as `Arena::bytes_used()` reports.
+    pub bytes_used: usize,
+    /// Chunks held, as `Arena::chunk_count()` reports.
+    pub chunk_count: usize,
+    /// Allocations made, as `Arena::allocation_count()` reports.
+    pub allocation_count: usize,
+    /// The most bytes ever allocated at once, as
+    /// `Arena::peak_bytes_used()` reports.
+    pub peak_bytes: usize,
+    /// Bytes lost to alignment padding, as `Arena::padding_bytes()` reports.
+    pub padding_bytes: usize,
+    /// Whether the arena has ever added a chunk, as `Arena::has_grown()`
+    /// reports.
+    pub has_grown: bool,
+    /// Allocations made before the first chunk was added, as
+    /// `Arena::allocations_before_first_growth()` reports.
+    pub allocations_before_first_growth: Option<usize>,
+}
+
+impl Snapshot {
+    /// Compare this snapshot with one taken `later`.
+    pub fn diff(&self, later: &Snapshot) -> UsageDelta {
+        UsageDelta {
+            bytes_used: later.bytes_used as isize - self.bytes_used as isize,
+            chunk_count: later.chunk_count as isize - self.chunk_count as isize,
+            allocation_count: later.allocation_count as isize - self.allocation_count as isize,
+        }
+    }
+}
+
+
+#[test]
+fn diff_around_allocations() {
+    let mut arena = super::Arena::with_capacity(64);
+    arena.allocator().alloc(0u64);
+    let before = arena.snapshot();
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc(1u32);
+        allocator.alloc_slice(&[2u8; 12]);
+    }
+    let after = arena.snapshot();
+    assert_eq!(before.diff(&after), UsageDelta { bytes_used: 16, chunk_count: 0, allocation_count: 2 });
+
+    arena.allocator().alloc([0u8; 100]);
+    let grown = arena.snapshot();
+    assert_eq!(after.diff(&grown).chunk_count, 1);
+
+    arena.reset();
+    let reset = arena.snapshot();
+    assert_eq!(grown.diff(&reset).bytes_used, -(grown.bytes_used as isize));
+    assert_eq!(reset.chunk_count, 1);
+}
+
+#[test]
+fn hint_after_growth() {
+    let mut arena = super::Arena::with_capacity(64);
+    for _ in 0..3 {
+        for _ in 0..30 {
+            arena.allocator().alloc([0u8; 10]);
+        }
+        arena.reset();
+    }
+    let hint = arena.tuning_hint();
+    assert_eq!(arena.peak_bytes_used(), 300);
+    assert_eq!(hint.suggested_capacity, 512);
+    assert!(hint.reserve_up_front);
+    assert!(hint.peak_chunk_count > 1);
+    assert_eq!(hint.padding_bytes, 0);
+
+    let mut tuned = super::Arena::with_capacity(hint.suggested_capacity);
+    for _ in 0..30 {
+        tuned.allocator().alloc([0u8; 10]);
+    }
+    assert!(!tuned.tuning_hint().reserve_up_front);
+}
+
+#[test]
+fn metrics_after_allocations() {
+    let mut arena = super::Arena::with_capacity(64);
+    {
+        let mut allocator = arena.allocator();
+        allocator.alloc(1u8);
+        allocator.alloc(2u32);
+        allocator.alloc_slice(&[3u8; 20]);
+    }
+    let before_growth = arena.metrics();
+    assert_eq!(before_growth, ArenaMetrics {
+        capacity: 64,
+        bytes_used: 28,
+        chunk_count: 1,
+        allocation_count: 3,
+        peak_bytes: 28,
+        padding_bytes: 3,
+        has_grown: false,
+        allocations_before_first_growth: None,
+    });
+
+    arena.allocator().alloc([0u8; 100]);
+    arena.reset();
+    let after_reset = arena.metrics();
+    assert_eq!(after_reset.bytes_used, 0);
+    assert_eq!(after_reset.peak_bytes, 128);
+    assert_eq!(after_reset.allocation_count, 4);
+    assert!(after_reset.has_grown);
+    assert_eq!(after_reset.allocations_before_first_growth, Some(3));
+}
@@ -0,0 +1,135 @@
+//! The memory that backs an arena's chunks.
+
+use std::slice;
+
+use super::CHUNK_FILL;
+
+/// A buffer an `Arena` can bump-allocate chunks out of.
+///
+/// `Vec<u8>` is the default; implement this to back an arena with memory
+/// from somewhere else, such as a memory-mapped region or a pool.
+///
+/// # Safety
+///
+/// The arena trusts the storage completely. The buffer at `as_ptr()` must
+/// be valid for `capacity()` bytes, all of them initialized, and must not
+/// move or change while the storage is alive, even when the storage value
+/// itself is moved. `len()` must return what was last passed to
+/// `set_len()` (zero for fresh storage), and `capacity()` must not change.
+pub unsafe trait ChunkStorage {
+    /// Create storage holding nothing, with room for at least `capacity`
+    /// bytes.
+    fn with_capacity(capacity: usize) -> Self;
+
+    /// The number of bytes the storage can hold.
+    fn capacity(&self) -> usize;
+
+    /// The number of bytes at the front of the buffer that are in use.
+    fn len(&self) -> usize;
+
+    /// Whether no bytes are in use.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Set the number of bytes in use.
+    ///
+    /// # Safety
+    ///
+    /// `len` must not be more than `capacity()`.
+    unsafe fn set_len(&mut self, len: usize);
+
+    /// Mark every byte as unused.
+    fn clear(&mut self) {
+        unsafe { self.set_len(0); }
+    }
+
+    /// A pointer to the start of the buffer.
+    fn as_ptr(&self) -> *const u8;
+
+    /// A mutable pointer to the start of the buffer.
+    fn as_mut_ptr(&mut self) -> *mut u8;
+
+    /// The bytes in use.
+    fn filled(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.len()) }
+    }
+
+    /// The bytes in use, mutably.
+    fn filled_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), self.len()) }
+    }
+}
+
+unsafe impl ChunkStorage for Vec<u8> {
+    fn with_capacity(capacity: usize) -> Vec<u8> {
+        // Start with every byte initialized, so padding between allocations
+        // can be read back through chunks() without exposing garbage.
+        let mut data = vec![CHUNK_FILL; capacity];
+        data.clear();
+        data
+    }
+
+    fn capacity(&self) -> usize {
+        Vec::capacity(self)
+    }
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    unsafe fn set_len(&mut self, len: usize) {
+        Vec::set_len(self, len)
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        Vec::as_ptr(self)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        Vec::as_mut_ptr(self)
+    }
+}
+
+
+#[cfg(test)]
+struct BoxStorage {
+    buf: Box<[u8]>,
+    len: usize,
+}
+
+#[cfg(test)]
+unsafe impl ChunkStorage for BoxStorage {
+    fn with_capacity(capacity: usize) -> BoxStorage {
+        BoxStorage { buf: vec![0xAB; capacity].into_boxed_slice(), len: 0 }
+    }
+
+    fn capacity(&self) -> usize { self.buf.len() }
+    fn len(&self) -> usize { self.len }
+    unsafe fn set_len(&mut self, len: usize) { self.len = len; }
+    fn as_ptr(&self) -> *const u8 { self.buf.as_ptr() }
+    fn as_mut_ptr(&mut self) -> *mut u8 { self.buf.as_mut_ptr() }
+}
+
+#[test]
+fn custom_storage() {
+    let mut arena = super::Arena::<BoxStorage>::with_storage(super::ArenaConfig {
+        initial_capacity: 8,
+        ..Default::default()
+    });
+    {
+        let mut allocator = arena.allocator();
+        let a = allocator.alloc(1u32);
+        let text = allocator.alloc_slice(b"grows past the first chunk");
+        let b = allocator.alloc(2u64);
+        assert_eq!((*a, &text[..6], *b), (1, &b"grows "[..], 2));
+    }
+    assert!(arena.has_grown());
+    assert_eq!(arena.validate(), Ok(()));
+
+    let contents: Vec<u8> = arena.chunks().flat_map(|chunk| chunk.iter().cloned()).collect();
+    assert_eq!(&contents[..4], &1u32.to_ne_bytes());
+
+    arena.reset();
+    assert_eq!(*arena.allocator().alloc(3u8), 3);
+}
@@ -0,0 +1,65 @@
+//! Nul-separated string tables built in an arena.
+
+use std::fmt;
+use std::mem;
+
+use super::Allocator;
+
+/// Builds a table of strings as one nul-separated blob in the arena, plus
+/// the offset of each string in it, as symbol tables in object files do.
+pub struct StringTable<'a> {
+    allocator: Allocator<'a>,
+    // Kept as the allocator's most recent allocation, so it grows in place
+    // while the chunk has room.
+    blob: &'a mut [u8],
+    offsets: Vec<u32>,
+}
+
+impl<'a> StringTable<'a> {
+    /// Construct an empty StringTable that allocates through `allocator`.
+    pub fn new(mut allocator: Allocator<'a>) -> StringTable<'a> {
+        let blob = allocator.alloc_slice(&[]);
+        StringTable { allocator, blob, offsets: Vec::new() }
+    }
+
+    /// Append `s` and a nul to the blob, returning the offset `s` starts at.
+    ///
+    /// Panics if the blob would outgrow a `u32` offset.
+    pub fn add(&mut self, s: &str) -> u32 {
+        let offset = self.blob.len();
+        assert!(offset + s.len() < u32::MAX as usize, "String table is too large");
+
+        let blob = mem::take(&mut self.blob);
+        self.blob = self.allocator.grow_slice(blob, s.len() + 1);
+        self.blob[offset..offset + s.len()].copy_from_slice(s.as_bytes());
+        self.offsets.push(offset as u32);
+        offset as u32
+    }
+
+    /// Finish the table, returning the blob and the offset of every string
+    /// added, in order.
+    pub fn finish(mut self) -> (&'a [u8], &'a [u32]) {
+        let offsets = self.allocator.alloc_slice(&self.offsets);
+        (self.blob, offsets)
+    }
+}
+
+impl<'a> fmt::Debug for StringTable<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("StringTable {{ strings: {}, bytes: {} }}", self.offsets.len(), self.blob.len()))
+    }
+}
+
+
+#[test]
+fn table_of_three() {
+    let mut arena = super::Arena::with_capacity(8);
+    let mut table = StringTable::new(arena.allocator());
+    assert_eq!(table.add("main"), 0);
+    assert_eq!(table.add(""), 5);
+    assert_eq!(table.add("printf"), 6);
+
+    let (blob, offsets) = table.finish();
+    assert_eq!(blob, b"main\0\0printf\0");
+    assert_eq!(offsets, &[0, 5, 6]);
+}
@@ -0,0 +1,40 @@
+//! Tree nodes whose children live in the same arena.
+
+/// A node of a tree built in an arena by `Allocator::alloc_node()`, holding
+/// a value and references to its children.
+///
+/// The children slice is allocated in the arena too, so a whole tree shares
+/// the arena's lifetime and is freed along with it.
+#[derive(Copy, Clone, Debug)]
+pub struct TreeNode<'a, T: 'a> {
+    /// The value held at this node.
+    pub value: T,
+    /// This node's children, in the order given to `alloc_node()`.
+    pub children: &'a [&'a TreeNode<'a, T>],
+}
+
+impl<'a, T> TreeNode<'a, T> {
+    /// Check whether this node has no children.
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+
+#[test]
+fn sum_small_tree() {
+    fn sum(node: &TreeNode<u32>) -> u32 {
+        node.value + node.children.iter().map(|child| sum(child)).sum::<u32>()
+    }
+
+    let mut arena = super::Arena::new();
+    let mut allocator = arena.allocator();
+    let left = allocator.alloc_node(2, &[]);
+    let right = allocator.alloc_node(3, &[]);
+    let root = allocator.alloc_node(1, &[left, right]);
+
+    assert!(!root.is_leaf());
+    assert!(root.children[0].is_leaf());
+    assert_eq!(root.children[1].value, 3);
+    assert_eq!(sum(root), 6);
+}
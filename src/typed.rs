@@ -0,0 +1,211 @@
+//! A pool holding values of a single type.
+
+use std::cmp;
+use std::fmt;
+use std::mem;
+use std::ops::{Index, IndexMut};
+use std::slice;
+
+/// An arena that only holds values of one type, `T`.
+///
+/// Because every allocation is a `T`, the pool knows exactly which values
+/// it holds and can hand them back out by iteration or aggregation.
+pub struct TypedArena<T> {
+    // Oldest chunk first. Chunks never reallocate, so a full chunk is
+    // followed by a fresh, larger one.
+    chunks: Vec<Vec<T>>,
+}
+
+impl<T: Copy> TypedArena<T> {
+    /// Construct a new TypedArena with room for about 1000 bytes of `T`s.
+    pub fn new() -> TypedArena<T> {
+        TypedArena::with_capacity(1000 / cmp::max(mem::size_of::<T>(), 1))
+    }
+
+    /// Construct a new TypedArena with room for `capacity` values before
+    /// it needs to grow.
+    ///
+    /// The chosen capacity does not limit the final size of the arena.
+    pub fn with_capacity(capacity: usize) -> TypedArena<T> {
+        TypedArena {
+            chunks: vec![Vec::with_capacity(cmp::max(capacity, 1))],
+        }
+    }
+
+    /// Allocate a copy of an object
+    pub fn alloc(&mut self, elem: T) -> &mut T {
+        let needs_chunk = {
+            let last = self.chunks.last().expect("TypedArena always has a chunk");
+            last.len() == last.capacity()
+        };
+        if needs_chunk {
+            let new_capacity = 2 * self.chunks.last().unwrap().capacity();
+            self.chunks.push(Vec::with_capacity(new_capacity));
+        }
+
+        let last = self.chunks.last_mut().unwrap();
+        last.push(elem);
+        last.last_mut().unwrap()
+    }
+
+    /// Get the number of values allocated in this arena.
+    pub fn count(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.len()).sum()
+    }
+
+    /// Iterate over the allocated values, in allocation order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            chunks: self.chunks.iter(),
+            current: [].iter(),
+        }
+    }
+
+    /// Move every allocated value, in allocation order, into a `Vec`.
+    pub fn into_vec(self) -> Vec<T> {
+        let mut values = Vec::with_capacity(self.count());
+        for chunk in self.chunks {
+            values.extend(chunk);
+        }
+        values
+    }
+
+    /// Find which chunk holds the value at `index`, and where in it.
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let mut remaining = index;
+        for (chunk_index, chunk) in self.chunks.iter().enumerate() {
+            if remaining < chunk.len() {
+                return (chunk_index, remaining);
+            }
+            remaining -= chunk.len();
+        }
+        panic!("index out of bounds: the count is {} but the index is {}", self.count(), index);
+    }
+
+    /// Combine all allocated values, in allocation order, into a single
+    /// result.
+    pub fn fold<B, F: FnMut(B, &T) -> B>(&self, init: B, f: F) -> B {
+        self.iter().fold(init, f)
+    }
+}
+
+impl<T: Copy> Index<usize> for TypedArena<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        let (chunk, offset) = self.locate(index);
+        &self.chunks[chunk][offset]
+    }
+}
+
+impl<T: Copy> IndexMut<usize> for TypedArena<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let (chunk, offset) = self.locate(index);
+        &mut self.chunks[chunk][offset]
+    }
+}
+
+impl<T: Copy> Default for TypedArena<T> {
+    fn default() -> TypedArena<T> {
+        TypedArena::new()
+    }
+}
+
+impl<T> fmt::Debug for TypedArena<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count: usize = self.chunks.iter().map(|chunk| chunk.len()).sum();
+        f.write_fmt(format_args!("TypedArena {{ count: {} }}", count))
+    }
+}
+
+/// Iterator over the values of a `TypedArena`, returned by `iter()`.
+pub struct Iter<'a, T: 'a> {
+    chunks: slice::Iter<'a, Vec<T>>,
+    current: slice::Iter<'a, T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            if let Some(elem) = self.current.next() {
+                return Some(elem);
+            }
+            match self.chunks.next() {
+                Some(chunk) => { self.current = chunk.iter(); }
+                None => { return None; }
+            }
+        }
+    }
+}
+
+
+#[test]
+fn typed_fold_across_chunks() {
+    #[derive(Copy, Clone)]
+    struct Particle {
+        mass: u64,
+        _charge: i8,
+    }
+
+    let mut arena = TypedArena::with_capacity(7);
+    let mut expected = 0;
+    for i in 0..1000 {
+        arena.alloc(Particle { mass: i * 3, _charge: -1 });
+        expected += i * 3;
+    }
+
+    assert!(arena.chunks.len() > 1);
+    assert_eq!(arena.count(), 1000);
+    assert_eq!(arena.fold(0, |total, p| total + p.mass), expected);
+    assert_eq!(arena.iter().map(|p| p.mass).collect::<Vec<_>>(),
+               (0..1000).map(|i| i * 3).collect::<Vec<_>>());
+}
+
+#[test]
+fn typed_empty() {
+    let arena: TypedArena<u32> = TypedArena::new();
+    assert_eq!(arena.count(), 0);
+    assert_eq!(arena.fold(5, |total, &x| total + x), 5);
+    assert!(arena.iter().next().is_none());
+}
+
+#[test]
+fn typed_index_across_chunks() {
+    let mut arena = TypedArena::with_capacity(3);
+    for i in 0..20u32 {
+        arena.alloc(i);
+    }
+    // Chunks hold 3, 6, 12, ... values.
+    assert_eq!(arena.chunks[0].len(), 3);
+    for &i in &[0, 2, 3, 8, 9, 19] {
+        assert_eq!(arena[i], i as u32);
+    }
+
+    arena[3] = 100;
+    arena[9] += 1;
+    assert_eq!(arena[3], 100);
+    assert_eq!(arena[9], 10);
+    assert_eq!(arena[2], 2);
+}
+
+#[test]
+#[should_panic]
+fn typed_index_out_of_bounds() {
+    let mut arena = TypedArena::with_capacity(3);
+    for i in 0..5u32 {
+        arena.alloc(i);
+    }
+    assert_eq!(arena[5], 5);
+}
+
+#[test]
+fn typed_into_vec() {
+    let mut arena = TypedArena::with_capacity(10);
+    for i in 0..100u32 {
+        arena.alloc(i * i);
+    }
+    assert!(arena.chunks.len() > 1);
+    assert_eq!(arena.into_vec(), (0..100).map(|i| i * i).collect::<Vec<u32>>());
+}